@@ -0,0 +1,308 @@
+use std::io::{self, stdout, Write};
+use std::thread::sleep;
+
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+
+use boiling_frog_dbus::{DbusEngine, GenericError, Level, ReadingStatus, SensorKind};
+
+use crate::cli::BarArgs;
+use crate::config::Config;
+use crate::headless::wait_for_first_readings;
+use crate::ui_format::{format_value, level_colour, sensor_line};
+
+/// The status bar `bar` prints for.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum BarFormat {
+    /// A JSON object per line, for a Waybar custom module with `"return-type": "json"`.
+    #[default]
+    Waybar,
+    /// The i3bar protocol, for i3bar or swaybar's `status_command`.
+    I3bar,
+    /// A line of text with colours, for a Polybar script module with `tail = true`.
+    Polybar,
+}
+
+/// One kind of reading, as shown in the bar.
+struct Block {
+    kind: SensorKind,
+    text: String,
+    level: Level,
+    /// How far the reading is towards critical, if its kind has thresholds.
+    fraction: Option<f64>,
+    status: ReadingStatus,
+}
+
+/// Prints the readings each time the line for the bar changes, until interrupted or stdout is
+/// closed.
+pub fn run(engine: &DbusEngine, config: &Config, args: &BarArgs) -> Result<(), GenericError> {
+    wait_for_first_readings(engine)?;
+    let kinds = if args.kinds.is_empty() {
+        vec![SensorKind::Temp, SensorKind::Fan]
+    } else {
+        args.kinds.clone()
+    };
+
+    let mut out = stdout().lock();
+    write_header(&mut out, args.format)?;
+
+    let mut last_line = None;
+    loop {
+        let blocks = blocks(engine, config, &kinds)?;
+        let line = match args.format {
+            BarFormat::Waybar => waybar(&blocks, &tooltip(engine, config)?),
+            BarFormat::I3bar => i3bar(&blocks),
+            BarFormat::Polybar => polybar(&blocks),
+        };
+        if last_line.as_ref() != Some(&line) {
+            write_line(&mut out, args.format, &line, last_line.is_none())?;
+            last_line = Some(line);
+        }
+        sleep(config.update_rate());
+    }
+}
+
+/// Writes the i3bar protocol's header & opens its endless array; the other formats have none.
+fn write_header(out: &mut impl Write, format: BarFormat) -> io::Result<()> {
+    if format == BarFormat::I3bar {
+        writeln!(out, "{}", json!({ "version": 1 }))?;
+        writeln!(out, "[")?;
+    }
+    Ok(())
+}
+
+/// Writes `line` & flushes it, separating each i3bar line after the first with a comma.
+fn write_line(
+    out: &mut impl Write,
+    format: BarFormat,
+    line: &str,
+    is_first: bool,
+) -> io::Result<()> {
+    // The i3bar protocol is an endless JSON array.
+    let separator = match format {
+        BarFormat::I3bar if !is_first => ",",
+        _ => "",
+    };
+    writeln!(out, "{separator}{line}")?;
+    out.flush()
+}
+
+fn blocks(
+    engine: &DbusEngine,
+    config: &Config,
+    kinds: &[SensorKind],
+) -> Result<Vec<Block>, GenericError> {
+    kinds
+        .iter()
+        .map(|kind| {
+            let reading = engine.reading(*kind)?;
+            let is_received = reading.status != ReadingStatus::NeverReceived;
+            Ok(Block {
+                kind: *kind,
+                text: if is_received {
+                    format_value(reading.value, &reading.units, config.temperature_scale)
+                } else {
+                    "…".to_string()
+                },
+                level: if is_received {
+                    config.level(*kind, reading.value)
+                } else {
+                    Level::Normal
+                },
+                fraction: config
+                    .thresholds(*kind)
+                    .filter(|_| is_received)
+                    .map(|thresholds| thresholds.fraction(reading.value)),
+                status: reading.status,
+            })
+        })
+        .collect()
+}
+
+/// Every sensor's latest reading, one per line.
+fn tooltip(engine: &DbusEngine, config: &Config) -> Result<String, GenericError> {
+    Ok(engine
+        .sensors()?
+        .iter()
+        .map(|snapshot| sensor_line(snapshot, config.temperature_scale))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+/// `{"text", "tooltip", "class", "percentage"}`, where the class is the highest
+/// [`Level::name`], plus `stale` if any reading is stale, & the percentage is of the way
+/// towards critical.
+fn waybar(blocks: &[Block], tooltip: &str) -> String {
+    let level = blocks
+        .iter()
+        .map(|block| block.level)
+        .max()
+        .unwrap_or_default();
+    let mut classes = vec![level.name()];
+    if blocks
+        .iter()
+        .any(|block| block.status == ReadingStatus::Stale)
+    {
+        classes.push("stale");
+    }
+
+    let mut object = Map::new();
+    object.insert("text".to_string(), json!(text(blocks)));
+    object.insert("tooltip".to_string(), json!(tooltip));
+    object.insert("class".to_string(), json!(classes));
+    if let Some(fraction) = blocks
+        .iter()
+        .filter_map(|block| block.fraction)
+        .reduce(f64::max)
+    {
+        object.insert(
+            "percentage".to_string(),
+            json!((fraction * 100.0).round() as u8),
+        );
+    }
+
+    Value::Object(object).to_string()
+}
+
+/// A block per kind of reading, coloured by its level & marked urgent when critical.
+fn i3bar(blocks: &[Block]) -> String {
+    let blocks: Vec<Value> = blocks
+        .iter()
+        .map(|block| {
+            let mut object = Map::new();
+            object.insert("name".to_string(), json!("boiling_frog"));
+            object.insert("instance".to_string(), json!(block.kind.name()));
+            object.insert("full_text".to_string(), json!(block.text));
+            if let Some(colour) = hex_colour(block.level) {
+                object.insert("color".to_string(), json!(colour));
+            }
+            if block.level == Level::Critical {
+                object.insert("urgent".to_string(), json!(true));
+            }
+            Value::Object(object)
+        })
+        .collect();
+
+    Value::Array(blocks).to_string()
+}
+
+/// The readings, each coloured by its level with Polybar's `%{F}` tags.
+fn polybar(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(|block| match hex_colour(block.level) {
+            Some(colour) => format!("%{{F{colour}}}{}%{{F-}}", block.text),
+            None => block.text.clone(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn text(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(|block| block.text.as_str())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// The colour of a reading at `level` as `#rrggbb`, or `None` to use the bar's own.
+fn hex_colour(level: Level) -> Option<String> {
+    level_colour(level).map(|(red, green, blue)| format!("#{red:02x}{green:02x}{blue:02x}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(kind: SensorKind, text: &str, level: Level, fraction: Option<f64>) -> Block {
+        Block {
+            kind,
+            text: text.to_string(),
+            level,
+            fraction,
+            status: ReadingStatus::Fresh,
+        }
+    }
+
+    fn written(format: BarFormat, lines: &[&str]) -> String {
+        let mut out = vec![];
+        write_header(&mut out, format).unwrap();
+        for (i, line) in lines.iter().enumerate() {
+            write_line(&mut out, format, line, i == 0).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_waybar_json_with_the_highest_level_and_fraction() {
+        let blocks = [
+            block(SensorKind::Temp, "61℃", Level::Hot, Some(0.724)),
+            block(SensorKind::Fan, "1200 RPM", Level::Normal, Some(0.3)),
+        ];
+        let json: Value = serde_json::from_str(&waybar(&blocks, "CPU 61℃\nfan1 1200 RPM")).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "text": "61℃ 1200 RPM",
+                "tooltip": "CPU 61℃\nfan1 1200 RPM",
+                "class": ["hot"],
+                "percentage": 72,
+            })
+        );
+    }
+
+    #[test]
+    fn marks_stale_waybar_output_and_leaves_out_an_unknown_percentage() {
+        let mut stale = block(SensorKind::Power, "12 W", Level::Normal, None);
+        stale.status = ReadingStatus::Stale;
+        let json: Value = serde_json::from_str(&waybar(&[stale], "")).unwrap();
+        assert_eq!(json["class"], json!(["normal", "stale"]));
+        assert!(json.get("percentage").is_none());
+    }
+
+    #[test]
+    fn writes_an_i3bar_block_per_kind_coloured_by_level() {
+        let blocks = [
+            block(SensorKind::Temp, "95℃", Level::Critical, Some(1.0)),
+            block(SensorKind::Fan, "1200 RPM", Level::Normal, None),
+        ];
+        let json: Value = serde_json::from_str(&i3bar(&blocks)).unwrap();
+        assert_eq!(
+            json,
+            json!([
+                {
+                    "name": "boiling_frog",
+                    "instance": "temp",
+                    "full_text": "95℃",
+                    "color": "#ff0000",
+                    "urgent": true,
+                },
+                { "name": "boiling_frog", "instance": "fan", "full_text": "1200 RPM" },
+            ])
+        );
+    }
+
+    #[test]
+    fn frames_i3bar_lines_as_an_endless_array_after_the_header() {
+        assert_eq!(
+            written(BarFormat::I3bar, &["[1]", "[2]", "[3]"]),
+            "{\"version\":1}\n[\n[1]\n,[2]\n,[3]\n"
+        );
+    }
+
+    #[test]
+    fn writes_other_formats_without_a_header_or_separators() {
+        assert_eq!(written(BarFormat::Waybar, &["{}", "{}"]), "{}\n{}\n");
+        assert_eq!(written(BarFormat::Polybar, &["a", "b"]), "a\nb\n");
+    }
+
+    #[test]
+    fn colours_polybar_readings_above_normal() {
+        let blocks = [
+            block(SensorKind::Temp, "70℃", Level::Warm, None),
+            block(SensorKind::Fan, "1200 RPM", Level::Normal, None),
+        ];
+        assert_eq!(polybar(&blocks), "%{F#ffb000}70℃%{F-} 1200 RPM");
+    }
+}
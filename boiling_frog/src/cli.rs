@@ -0,0 +1,319 @@
+use std::path::PathBuf;
+
+use clap::{value_parser, Args, Parser, Subcommand};
+
+use boiling_frog_dbus::{Field, GenericError, Rule, SensorKind, TemperatureScale};
+
+use crate::bar::BarFormat;
+use crate::config::Config;
+use crate::watch::Format;
+
+/// Shows the hottest temperature & fastest fan speed of a computer, along with its other sensor
+/// readings.
+#[derive(Clone, Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Reads settings from FILE instead of `$XDG_CONFIG_HOME/boiling_frog/config.toml`.
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// How often readings are refreshed, in milliseconds. Overrides `update_rate_ms`.
+    #[arg(long, global = true, value_name = "MS", value_parser = value_parser!(u64).range(1..))]
+    pub update_rate: Option<u64>,
+
+    /// Only reads sensors matching RULE, which is `[FIELD=]GLOB` or `FIELD~REGEX`. FIELD is
+    /// `path`, `interface`, `label` (the default) or `kind`. Can be repeated, & adds to the config
+    /// file's `include` rules.
+    #[arg(long = "sensor", global = true, value_name = "RULE", value_parser = parse_rule)]
+    pub sensors: Vec<Rule>,
+
+    /// Shows temperatures in Fahrenheit. Overrides `temperature_scale`.
+    #[arg(long, global = true)]
+    pub fahrenheit: bool,
+
+    /// Appends every sensor reading to FILE, as CSV if it ends in `.csv` & JSON Lines otherwise.
+    /// In the window, recording starts straight away & the "Record" button records to FILE.
+    #[arg(long, global = true, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Lists the sensors & exits, like the `list` command.
+    #[arg(long)]
+    pub list_sensors: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Subcommand)]
+pub enum Command {
+    /// Shows the readings in a window. This is the default.
+    #[default]
+    Gui,
+    /// Lists the sensors that are read.
+    List,
+    /// Prints the aggregated & per-sensor readings each time they're refreshed, until
+    /// interrupted.
+    Watch(WatchArgs),
+    /// Prints the readings for a status bar such as Waybar, i3bar or Polybar each time they
+    /// change, until interrupted.
+    Bar(BarArgs),
+    /// Prints every reading once.
+    Dump,
+    /// Compares how hot the machine has run & how fast its fans have spun lately with a month
+    /// or so ago, from the baseline recorded while reading the sensors.
+    Drift,
+}
+
+#[derive(Args, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct WatchArgs {
+    /// How the readings are printed.
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+
+    /// Only prints the readings that have changed since they were last printed.
+    #[arg(long)]
+    pub on_change: bool,
+
+    /// Also sends a desktop notification when a reading crosses one of its thresholds.
+    #[arg(long)]
+    pub notify: bool,
+}
+
+#[derive(Args, Clone, Debug, Default, PartialEq)]
+pub struct BarArgs {
+    /// What the status bar expects.
+    #[arg(long, value_enum, default_value_t)]
+    pub format: BarFormat,
+
+    /// A kind of reading to show, e.g. `power`. Can be repeated, & defaults to `temp` & `fan`.
+    #[arg(long = "kind", value_name = "KIND", value_parser = parse_kind)]
+    pub kinds: Vec<SensorKind>,
+
+    /// Also sends a desktop notification when a reading crosses one of its thresholds.
+    #[arg(long)]
+    pub notify: bool,
+}
+
+impl Cli {
+    pub fn command(&self) -> Command {
+        if self.list_sensors {
+            Command::List
+        } else {
+            self.command.clone().unwrap_or_default()
+        }
+    }
+
+    /// These arguments with any that `later` gives in their place, for when the app is launched
+    /// again while it's running.
+    #[cfg(feature = "gui")]
+    pub fn overridden_by(&self, later: &Cli) -> Cli {
+        Cli {
+            command: self.command.clone(),
+            config: later.config.clone().or_else(|| self.config.clone()),
+            update_rate: later.update_rate.or(self.update_rate),
+            sensors: if later.sensors.is_empty() {
+                self.sensors.clone()
+            } else {
+                later.sensors.clone()
+            },
+            fahrenheit: self.fahrenheit || later.fahrenheit,
+            record: later.record.clone().or_else(|| self.record.clone()),
+            list_sensors: self.list_sensors,
+        }
+    }
+
+    /// The config file that's read, which is watched for changes.
+    #[cfg(feature = "gui")]
+    pub fn config_path(&self) -> Option<PathBuf> {
+        self.config.clone().or_else(crate::config::config_path)
+    }
+
+    /// Reads the config file & applies the arguments that override it.
+    pub fn load_config(&self) -> Result<Config, GenericError> {
+        let mut config = Config::load(self.config.as_deref())?;
+        if let Some(update_rate) = self.update_rate {
+            config.update_rate_ms = update_rate;
+        }
+        config.filters.include.extend(self.sensors.iter().cloned());
+        if self.fahrenheit {
+            config.temperature_scale = TemperatureScale::Fahrenheit;
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_rule(arg: &str) -> Result<Rule, GenericError> {
+    // Only a field name before the separator makes it a field, so a bare glob can contain either.
+    if let Some(index) = arg.find(['=', '~']) {
+        if let Some(field) = parse_field(&arg[..index]) {
+            let pattern = &arg[index + 1..];
+            return if arg[index..].starts_with('~') {
+                Rule::regex(field, pattern)
+            } else {
+                Rule::glob(field, pattern)
+            };
+        }
+    }
+
+    Rule::glob(Field::Label, arg)
+}
+
+fn parse_kind(name: &str) -> Result<SensorKind, String> {
+    SensorKind::ALL
+        .into_iter()
+        .find(|kind| kind.name() == name)
+        .ok_or_else(|| format!("there's no kind of sensor called {name}"))
+}
+
+fn parse_field(name: &str) -> Option<Field> {
+    match name {
+        "path" => Some(Field::Path),
+        "interface" => Some(Field::Interface),
+        "label" => Some(Field::Label),
+        "kind" => Some(Field::Kind),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use boiling_frog_dbus::Sensor;
+
+    use super::*;
+
+    fn sensor(path: &str, interface: &str, label: &str, kind: SensorKind) -> Sensor {
+        Sensor {
+            id: path.to_string(),
+            path: path.to_string(),
+            interface: interface.to_string(),
+            label: label.to_string(),
+            kind,
+            units: kind.units(),
+        }
+    }
+
+    fn cpu() -> Sensor {
+        sensor(
+            "/sys/class/hwmon/hwmon2/temp1_input",
+            "k10temp",
+            "Tctl",
+            SensorKind::Temp,
+        )
+    }
+
+    fn matches(rule: &str, sensor: &Sensor) -> bool {
+        parse_rule(rule).unwrap().matches(sensor)
+    }
+
+    #[test]
+    fn a_bare_glob_matches_the_label() {
+        assert!(matches("Tc*", &cpu()));
+        assert!(!matches("Tc", &cpu()));
+        assert!(!matches("k10temp", &cpu()));
+    }
+
+    #[test]
+    fn a_field_before_equals_picks_the_field_to_glob() {
+        assert!(matches("interface=k10*", &cpu()));
+        assert!(matches("path=/sys/class/hwmon/*/temp1_input", &cpu()));
+        assert!(matches("kind=temp", &cpu()));
+        assert!(!matches("kind=fan", &cpu()));
+        assert!(matches("label=Tctl", &cpu()));
+    }
+
+    #[test]
+    fn a_field_before_tilde_picks_the_field_to_search_with_a_regex() {
+        assert!(matches("path~hwmon[0-9]+/temp", &cpu()));
+        assert!(matches("label~ctl", &cpu()));
+        assert!(!matches("label~^ctl", &cpu()));
+        assert!(!matches("interface~amdgpu", &cpu()));
+    }
+
+    #[test]
+    fn anything_else_before_a_separator_is_part_of_the_glob() {
+        let sensor = sensor("/fan", "", "a=b~c", SensorKind::Fan);
+
+        assert!(matches("a=b~c", &sensor));
+        assert!(matches("a=*", &sensor));
+        assert!(!matches("label=b~c", &sensor));
+    }
+
+    #[test]
+    fn bad_patterns_are_rejected() {
+        assert!(parse_rule("label~(").is_err());
+        assert!(parse_rule("path=[").is_err());
+    }
+
+    #[test]
+    fn parses_kinds_by_name() {
+        assert_eq!(parse_kind("power"), Ok(SensorKind::Power));
+        assert!(parse_kind("Power").is_err());
+    }
+
+    #[test]
+    fn collects_repeated_sensor_rules() {
+        let cli = Cli::try_parse_from([
+            "boiling_frog",
+            "list",
+            "--sensor",
+            "Tctl",
+            "--sensor",
+            "kind~^f",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.command(), Command::List);
+        assert_eq!(cli.sensors.len(), 2);
+        assert!(cli.sensors[0].matches(&cpu()));
+        assert!(!cli.sensors[1].matches(&cpu()));
+        assert!(cli.sensors[1].matches(&sensor("/fan", "", "Fan", SensorKind::Fan)));
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn a_later_launch_overrides_only_the_arguments_it_gives() {
+        let first = Cli::try_parse_from([
+            "boiling_frog",
+            "--config",
+            "first.toml",
+            "--update-rate",
+            "500",
+            "--sensor",
+            "Tctl",
+        ])
+        .unwrap();
+        let later = Cli::try_parse_from([
+            "boiling_frog",
+            "--fahrenheit",
+            "--record",
+            "readings.csv",
+            "--sensor",
+            "kind=fan",
+            "--sensor",
+            "kind=temp",
+        ])
+        .unwrap();
+
+        let cli = first.overridden_by(&later);
+
+        assert_eq!(cli.config, Some(PathBuf::from("first.toml")));
+        assert_eq!(cli.update_rate, Some(500));
+        assert_eq!(cli.sensors.len(), 2);
+        assert!(!cli.sensors[0].matches(&cpu()));
+        assert!(cli.fahrenheit);
+        assert_eq!(cli.record, Some(PathBuf::from("readings.csv")));
+        assert_eq!(
+            first
+                .overridden_by(&Cli::try_parse_from(["boiling_frog"]).unwrap())
+                .sensors
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn reports_a_bad_sensor_rule() {
+        assert!(Cli::try_parse_from(["boiling_frog", "--sensor", "label~("]).is_err());
+    }
+}
@@ -1,4 +1,713 @@
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::env::var_os;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "gui")]
+use std::time::UNIX_EPOCH;
+use std::time::{Duration, SystemTime};
 
-pub const MARGIN: i32 = 12;
-pub const UPDATE_RATE: Duration = Duration::from_millis(100);
+use serde::Deserialize;
+
+use boiling_frog_dbus::{
+    Aggregation, AlertSettings, Baseline, Drift, GenericError, Level, SensorFilter, SensorKind,
+    SensorSnapshot, TemperatureScale, Thresholds, TrendSettings, ACTIVE_SENSORS_PATH,
+    INDICATOR_SENSORS_SERVICE,
+};
+#[cfg(feature = "sqlite")]
+use boiling_frog_dbus::{DatabaseSettings, Retention};
+
+use crate::error::ConfigError;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// The fewest readings under a condition, a minute apart, that its average is compared from.
+const MIN_BASELINE_SAMPLES: u64 = 30;
+
+/// Settings read from `config.toml`. Anything it doesn't set keeps its default.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// How often the window is refreshed, in milliseconds. Defaults to 100.
+    pub update_rate_ms: u64,
+    /// The space around each widget, in pixels. Defaults to 12.
+    pub margin: i32,
+    /// The size of each panel's value, in points. Defaults to 39.
+    pub font_size: f64,
+    /// The height of the graph under each panel's value, in pixels, or 0 to hide the graphs.
+    /// Defaults to 80.
+    pub graph_height: i32,
+    /// Makes panels blink while their reading is critical. Defaults to false.
+    pub blink_critical: bool,
+    /// How long a reading can go without an update before it's greyed out, in seconds. Defaults
+    /// to 30.
+    pub stale_after_secs: u64,
+    /// The scale temperatures are shown in, whatever the sensors report them in. Defaults to
+    /// `"celsius"`.
+    pub temperature_scale: TemperatureScale,
+    /// Which sensors to read. Setting `exclude` replaces the default rule that excludes
+    /// Indicator Sensors' virtual sensors.
+    pub filters: SensorFilter,
+    /// The panels to show, in order, at most one per kind of sensor. Defaults to the highest
+    /// reading of each kind, with temperature & fan speed always shown and the others only once
+    /// there's a sensor for them.
+    pub panels: Vec<PanelConfig>,
+    /// The readings at which each kind of sensor becomes warm, hot & critical, in the kind's
+    /// units, e.g. ℃ for temperatures whatever `temperature_scale` is, unless its panel sets its
+    /// own. Defaults to 70, 85 & 95℃ for temperatures & none for the other kinds.
+    pub thresholds: HashMap<SensorKind, Thresholds>,
+    /// How fast each kind of reading has to rise, & for how long, to count as rising steadily
+    /// even if it hasn't crossed a threshold. Defaults to 0.5℃ a minute for 10 minutes for
+    /// temperatures & none for the other kinds.
+    pub trends: HashMap<SensorKind, TrendConfig>,
+    pub notifications: NotificationsConfig,
+    pub baseline: BaselineConfig,
+    pub database: DatabaseConfig,
+    pub dbus: DbusConfig,
+}
+
+/// One panel of the window, showing the aggregate of every sensor of a kind.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PanelConfig {
+    pub kind: SensorKind,
+    /// Defaults to `"max"`.
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    /// Defaults to a title describing the aggregation, e.g. "Maximum Temperature".
+    pub title: Option<String>,
+    /// Hides the panel while there are no sensors of its kind. Defaults to false.
+    #[serde(default)]
+    pub hide_when_empty: bool,
+    /// Replaces the thresholds for the panel's kind in [`Config::thresholds`].
+    pub thresholds: Option<Thresholds>,
+}
+
+impl PanelConfig {
+    pub fn title(&self) -> String {
+        self.title
+            .clone()
+            .unwrap_or_else(|| self.aggregation.title(self.kind))
+    }
+
+    /// Whether the panel should be shown, given the sensors found so far.
+    pub fn is_wanted(&self, snapshots: &[SensorSnapshot]) -> bool {
+        !self.hide_when_empty || snapshots.iter().any(|snapshot| snapshot.kind == self.kind)
+    }
+}
+
+/// In a config file, this is e.g. `{ rate = 0.5, period_secs = 600 }`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TrendConfig {
+    /// In the kind's units per minute.
+    pub rate: f64,
+    pub period_secs: u64,
+}
+
+/// When a desktop notification is sent about a reading crossing one of its thresholds.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationsConfig {
+    /// Sends notifications from the window. Defaults to true.
+    pub enabled: bool,
+    /// How far below a threshold a reading has to fall before crossing it again sends another
+    /// notification, as a fraction of the threshold. Defaults to 0.05.
+    pub hysteresis: f64,
+    /// The shortest time between notifications about the same reading, unless it gets worse, in
+    /// seconds. Defaults to 300.
+    pub cooldown_secs: u64,
+    /// Sends notifications about each sensor as well as the panels' readings. Defaults to false.
+    pub per_sensor: bool,
+}
+
+impl NotificationsConfig {
+    pub fn alert_settings(&self) -> AlertSettings {
+        AlertSettings {
+            hysteresis: self.hysteresis,
+            cooldown: Duration::from_secs(self.cooldown_secs),
+            per_sensor: self.per_sensor,
+        }
+    }
+}
+
+/// How the machine's temperatures & fan speeds are kept for months, to show when it runs hotter
+/// than it used to under the same conditions.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BaselineConfig {
+    /// Records the baseline while the window or another command that reads the sensors is
+    /// running. Defaults to true.
+    pub enabled: bool,
+    /// Defaults to `$XDG_DATA_HOME/boiling_frog/baseline-<machine id>.json`.
+    pub path: Option<PathBuf>,
+    /// How many days of recent readings are compared. Defaults to 7.
+    pub period_days: u64,
+    /// How many days earlier the readings they're compared with are. Defaults to 30.
+    pub age_days: u64,
+    /// The rise in temperature worth reporting, in ℃. Defaults to 5.
+    pub temp_rise: f64,
+    /// The rise in fan speed worth reporting, in RPM. Defaults to 300.
+    pub fan_rise: f64,
+}
+
+impl BaselineConfig {
+    /// Where the baseline is kept, if there's anywhere to keep it.
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path.clone().or_else(|| data_path("baseline", "json"))
+    }
+
+    pub fn period(&self) -> Duration {
+        Duration::from_secs(self.period_days * SECONDS_PER_DAY)
+    }
+
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(self.age_days * SECONDS_PER_DAY)
+    }
+
+    /// How the recent readings in `baseline` compare with the earlier ones.
+    pub fn compare(&self, baseline: &Baseline) -> Vec<Drift> {
+        baseline.compare(
+            SystemTime::now(),
+            self.period(),
+            self.age(),
+            MIN_BASELINE_SAMPLES,
+        )
+    }
+
+    /// Whether `drift` is a rise worth reporting.
+    pub fn is_worrying(&self, drift: &Drift) -> bool {
+        match drift.kind {
+            SensorKind::Temp => drift.change() >= self.temp_rise,
+            SensorKind::Fan => drift.change() >= self.fan_rise,
+            _ => false,
+        }
+    }
+}
+
+/// Where every reading is recorded, to keep it after the app exits.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Records readings in an SQLite database. Defaults to false.
+    pub enabled: bool,
+    /// Defaults to `$XDG_DATA_HOME/boiling_frog/readings-<machine id>.sqlite`.
+    pub path: Option<PathBuf>,
+    /// How many days every reading is kept for. Defaults to 7.
+    pub readings_days: u64,
+    /// How many days the minimum, mean & maximum of each minute are kept for. Defaults to 90.
+    pub minutes_days: u64,
+    /// How many days the minimum, mean & maximum of each hour are kept for. Defaults to 730.
+    pub hours_days: u64,
+    /// How often the readings are written, in seconds. Defaults to 5.
+    pub batch_secs: u64,
+}
+
+impl DatabaseConfig {
+    /// Where the database is kept, if there's anywhere to keep it.
+    #[cfg(feature = "sqlite")]
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path
+            .clone()
+            .or_else(|| data_path("readings", "sqlite"))
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn settings(&self, path: &Path) -> DatabaseSettings {
+        let days = |days: u64| Duration::from_secs(days * SECONDS_PER_DAY);
+        DatabaseSettings {
+            path: path.to_path_buf(),
+            retention: Retention {
+                readings: days(self.readings_days),
+                minutes: days(self.minutes_days),
+                hours: days(self.hours_days),
+            },
+            batch_every: Duration::from_secs(self.batch_secs),
+        }
+    }
+}
+
+/// Where Indicator Sensors publishes its sensors on the session bus.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DbusConfig {
+    /// Defaults to `"com.github.alexmurray.IndicatorSensors"`.
+    pub service: String,
+    /// Defaults to `"/com/github/alexmurray/IndicatorSensors/ActiveSensors"`.
+    pub path: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            update_rate_ms: 100,
+            margin: 12,
+            font_size: 39.0,
+            graph_height: 80,
+            blink_critical: false,
+            stale_after_secs: 30,
+            temperature_scale: TemperatureScale::default(),
+            filters: SensorFilter::default(),
+            panels: default_panels(),
+            thresholds: default_thresholds(),
+            trends: HashMap::from([(
+                SensorKind::Temp,
+                TrendConfig {
+                    rate: 0.5,
+                    period_secs: 600,
+                },
+            )]),
+            notifications: NotificationsConfig::default(),
+            baseline: BaselineConfig::default(),
+            database: DatabaseConfig::default(),
+            dbus: DbusConfig::default(),
+        }
+    }
+}
+
+impl Default for BaselineConfig {
+    fn default() -> Self {
+        BaselineConfig {
+            enabled: true,
+            path: None,
+            period_days: 7,
+            age_days: 30,
+            temp_rise: 5.0,
+            fan_rise: 300.0,
+        }
+    }
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        let settings = AlertSettings::default();
+        NotificationsConfig {
+            enabled: true,
+            hysteresis: settings.hysteresis,
+            cooldown_secs: settings.cooldown.as_secs(),
+            per_sensor: settings.per_sensor,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            enabled: false,
+            path: None,
+            readings_days: 7,
+            minutes_days: 90,
+            hours_days: 730,
+            batch_secs: 5,
+        }
+    }
+}
+
+impl Default for DbusConfig {
+    fn default() -> Self {
+        DbusConfig {
+            service: INDICATOR_SENSORS_SERVICE.to_string(),
+            path: ACTIVE_SENSORS_PATH.to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file at `path`, or at [`config_path`] if that's `None`, in which case
+    /// the defaults are returned if there's no config file.
+    pub fn load(path: Option<&Path>) -> Result<Config, GenericError> {
+        match path {
+            Some(path) => Config::read(path, false),
+            None => match config_path() {
+                Some(path) => Config::read(&path, true),
+                None => Ok(Config::default()),
+            },
+        }
+    }
+
+    /// Reads the config file at `path`, returning the defaults if it's `is_optional` & missing.
+    fn read(path: &Path, is_optional: bool) -> Result<Config, GenericError> {
+        let error = |message: String| ConfigError {
+            path: path.display().to_string(),
+            message,
+        };
+        let contents = match read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if is_optional && e.kind() == ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(e) => return Err(Box::new(error(e.to_string()))),
+        };
+        let config: Config = toml::from_str(&contents).map_err(|e| error(e.to_string()))?;
+        config.validate().map_err(error)?;
+
+        Ok(config)
+    }
+
+    pub fn update_rate(&self) -> Duration {
+        Duration::from_millis(self.update_rate_ms)
+    }
+
+    pub fn stale_after(&self) -> Duration {
+        Duration::from_secs(self.stale_after_secs)
+    }
+
+    /// The thresholds for `kind` readings: its panel's if it sets them, otherwise the kind's.
+    pub fn thresholds(&self, kind: SensorKind) -> Option<&Thresholds> {
+        self.panels
+            .iter()
+            .find(|panel| panel.kind == kind)
+            .and_then(|panel| panel.thresholds.as_ref())
+            .or_else(|| self.thresholds.get(&kind))
+    }
+
+    /// The thresholds for every kind that has them, as [`Config::thresholds`].
+    pub fn all_thresholds(&self) -> HashMap<SensorKind, Thresholds> {
+        SensorKind::ALL
+            .iter()
+            .filter_map(|kind| Some((*kind, *self.thresholds(*kind)?)))
+            .collect()
+    }
+
+    pub fn trend_settings(&self) -> HashMap<SensorKind, TrendSettings> {
+        self.trends
+            .iter()
+            .map(|(kind, trend)| {
+                let settings = TrendSettings {
+                    rate: trend.rate,
+                    period: Duration::from_secs(trend.period_secs),
+                };
+                (*kind, settings)
+            })
+            .collect()
+    }
+
+    /// How worrying a `kind` reading of `value` is. Kinds without thresholds are always normal.
+    pub fn level(&self, kind: SensorKind, value: f64) -> Level {
+        self.thresholds(kind)
+            .map(|thresholds| thresholds.level(value))
+            .unwrap_or_default()
+    }
+
+    /// Checks the values that parse but make no sense.
+    fn validate(&self) -> Result<(), String> {
+        if self.update_rate_ms == 0 {
+            return Err("update_rate_ms must be more than 0".to_string());
+        }
+        if self.margin < 0 {
+            return Err("margin can't be negative".to_string());
+        }
+        if self.font_size.is_nan() || self.font_size <= 0.0 {
+            return Err("font_size must be more than 0".to_string());
+        }
+        if self.graph_height < 0 {
+            return Err("graph_height can't be negative".to_string());
+        }
+        if !(0.0..1.0).contains(&self.notifications.hysteresis) {
+            return Err("notifications.hysteresis must be from 0 to less than 1".to_string());
+        }
+
+        let baseline = &self.baseline;
+        if baseline.period_days == 0 || baseline.age_days < baseline.period_days {
+            return Err(
+                "baseline.period_days must be more than 0 & no more than age_days".to_string(),
+            );
+        }
+        if baseline.temp_rise.is_nan() || baseline.fan_rise.is_nan() {
+            return Err("baseline.temp_rise & fan_rise must be numbers".to_string());
+        }
+
+        let database = &self.database;
+        if database.batch_secs == 0 {
+            return Err("database.batch_secs must be more than 0".to_string());
+        }
+        if database.readings_days > database.minutes_days
+            || database.minutes_days > database.hours_days
+        {
+            return Err(
+                "database.readings_days <= minutes_days <= hours_days must hold".to_string(),
+            );
+        }
+
+        // The engine aggregates each kind of sensor one way.
+        let mut kinds = HashSet::new();
+        for panel in &self.panels {
+            if !kinds.insert(panel.kind) {
+                return Err(format!(
+                    "there's more than one panel for {} sensors",
+                    panel.kind.name()
+                ));
+            }
+        }
+
+        let panel_thresholds = self
+            .panels
+            .iter()
+            .filter_map(|panel| Some((&panel.kind, panel.thresholds.as_ref()?)));
+        for (kind, thresholds) in self.thresholds.iter().chain(panel_thresholds) {
+            if !thresholds.is_ordered() {
+                return Err(format!(
+                    "the {} thresholds must be warm <= hot <= critical",
+                    kind.name()
+                ));
+            }
+        }
+
+        for (kind, trend) in &self.trends {
+            if trend.rate.is_nan() || trend.rate <= 0.0 || trend.period_secs == 0 {
+                return Err(format!(
+                    "the {} trend's rate & period_secs must be more than 0",
+                    kind.name()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn default_thresholds() -> HashMap<SensorKind, Thresholds> {
+    HashMap::from([(
+        SensorKind::Temp,
+        Thresholds {
+            warm: 70.0,
+            hot: 85.0,
+            critical: 95.0,
+        },
+    )])
+}
+
+fn default_panels() -> Vec<PanelConfig> {
+    SensorKind::ALL
+        .iter()
+        // Readings in unrecognised units can't be meaningfully aggregated.
+        .filter(|kind| **kind != SensorKind::Other)
+        .map(|kind| PanelConfig {
+            kind: *kind,
+            aggregation: Aggregation::Max,
+            title: None,
+            hide_when_empty: !matches!(kind, SensorKind::Temp | SensorKind::Fan),
+            thresholds: None,
+        })
+        .collect()
+}
+
+/// `$XDG_CONFIG_HOME/boiling_frog/config.toml`, falling back to `~/.config` if `XDG_CONFIG_HOME`
+/// isn't set.
+pub fn config_path() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+        .map(|config_home| config_home.join("boiling_frog").join("config.toml"))
+}
+
+/// A new file in `$XDG_DATA_HOME/boiling_frog/recordings` for the window to record readings to.
+#[cfg(feature = "gui")]
+pub fn recording_path() -> Option<PathBuf> {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|data_home| {
+        data_home
+            .join("boiling_frog")
+            .join("recordings")
+            .join(format!("recording-{seconds}.csv"))
+    })
+}
+
+/// `$XDG_DATA_HOME/boiling_frog/<name>-<machine id>.<extension>`, falling back to
+/// `~/.local/share` if `XDG_DATA_HOME` isn't set, so that a home directory shared between
+/// machines keeps a file for each.
+fn data_path(name: &str, extension: &str) -> Option<PathBuf> {
+    let name = match read_to_string("/etc/machine-id") {
+        Ok(machine_id) if !machine_id.trim().is_empty() => {
+            format!("{name}-{}.{extension}", machine_id.trim())
+        }
+        _ => format!("{name}.{extension}"),
+    };
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+        .map(|data_home| data_home.join("boiling_frog").join(name))
+}
+
+/// The directory in the XDG base directory `variable`, or `fallback` in the home directory.
+fn xdg_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+
+    /// Writes `contents` to a config file & reads it back. The directory goes away when it's
+    /// dropped, so it's returned too.
+    fn read(contents: &str) -> (TempDir, Result<Config, GenericError>) {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("config.toml");
+        write(&path, contents).unwrap();
+        let config = Config::load(Some(&path));
+        (directory, config)
+    }
+
+    /// The reason `contents` is rejected.
+    fn rejection(contents: &str) -> String {
+        let (directory, config) = read(contents);
+        let message = config.unwrap_err().to_string();
+        let path = directory.path().join("config.toml");
+        assert!(
+            message.starts_with(&format!("Invalid config in {}: ", path.display())),
+            "{message}"
+        );
+        message
+    }
+
+    fn assert_rejected(contents: &str, reason: &str) {
+        let message = rejection(contents);
+        assert!(
+            message.contains(reason),
+            "{message:?} doesn't mention {reason:?}"
+        );
+    }
+
+    #[test]
+    fn reads_a_valid_config() {
+        let (_directory, config) = read(
+            r#"
+            update_rate_ms = 250
+            temperature_scale = "fahrenheit"
+
+            [[panels]]
+            kind = "temp"
+            aggregation = "median"
+            thresholds = { warm = 60.0, hot = 70.0, critical = 80.0 }
+            "#,
+        );
+        let config = config.unwrap();
+
+        assert_eq!(config.update_rate(), Duration::from_millis(250));
+        assert_eq!(config.temperature_scale, TemperatureScale::Fahrenheit);
+        assert_eq!(config.panels.len(), 1);
+        assert_eq!(config.panels[0].aggregation, Aggregation::Median);
+        assert_eq!(config.thresholds(SensorKind::Temp).unwrap().warm, 60.0);
+        assert_eq!(config.margin, Config::default().margin);
+    }
+
+    #[test]
+    fn falls_back_to_the_defaults_without_a_default_config_file() {
+        let directory = tempdir().unwrap();
+
+        let config = Config::read(&directory.path().join("config.toml"), true).unwrap();
+
+        let defaults = Config::default();
+        assert_eq!(config.update_rate_ms, defaults.update_rate_ms);
+        assert_eq!(config.panels.len(), defaults.panels.len());
+        assert_eq!(config.all_thresholds(), defaults.all_thresholds());
+    }
+
+    #[test]
+    fn rejects_a_missing_config_file_that_was_asked_for() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("config.toml");
+
+        let message = Config::load(Some(&path)).unwrap_err().to_string();
+
+        assert!(
+            message.starts_with(&format!("Invalid config in {}: ", path.display())),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert_rejected("update_rate = 100", "unknown field `update_rate`");
+        assert_rejected("[notifications]\nenable = false", "unknown field `enable`");
+        assert_rejected(
+            "[[panels]]\nkind = \"temp\"\ncolour = \"red\"",
+            "unknown field `colour`",
+        );
+    }
+
+    #[test]
+    fn rejects_values_of_the_wrong_type() {
+        assert_rejected("update_rate_ms = \"fast\"", "update_rate_ms");
+        assert_rejected(
+            "temperature_scale = \"rankine\"",
+            "unknown variant `rankine`",
+        );
+    }
+
+    #[test]
+    fn rejects_a_bad_filter() {
+        assert_rejected(
+            "[filters]\ninclude = [{ field = \"label\", regex = \"(\" }]",
+            "regex parse error",
+        );
+        assert_rejected(
+            "[filters]\ninclude = [{ field = \"label\" }]",
+            "either a glob or a regex",
+        );
+    }
+
+    #[test]
+    fn rejects_values_that_make_no_sense() {
+        let rejections = [
+            ("update_rate_ms = 0", "update_rate_ms must be more than 0"),
+            ("margin = -1", "margin can't be negative"),
+            ("font_size = 0.0", "font_size must be more than 0"),
+            ("font_size = nan", "font_size must be more than 0"),
+            ("graph_height = -1", "graph_height can't be negative"),
+            (
+                "[notifications]\nhysteresis = 1.0",
+                "notifications.hysteresis must be from 0 to less than 1",
+            ),
+            (
+                "[baseline]\nperiod_days = 0",
+                "baseline.period_days must be more than 0",
+            ),
+            (
+                "[baseline]\nperiod_days = 40",
+                "baseline.period_days must be more than 0 & no more than age_days",
+            ),
+            (
+                "[baseline]\ntemp_rise = nan",
+                "baseline.temp_rise & fan_rise must be numbers",
+            ),
+            (
+                "[database]\nbatch_secs = 0",
+                "database.batch_secs must be more than 0",
+            ),
+            (
+                "[database]\nreadings_days = 100",
+                "database.readings_days <= minutes_days <= hours_days must hold",
+            ),
+            (
+                "[[panels]]\nkind = \"fan\"\n[[panels]]\nkind = \"fan\"",
+                "there's more than one panel for fan sensors",
+            ),
+            (
+                "[thresholds]\ntemp = { warm = 90.0, hot = 85.0, critical = 95.0 }",
+                "the temp thresholds must be warm <= hot <= critical",
+            ),
+            (
+                "[[panels]]\nkind = \"temp\"\nthresholds = { warm = 70.0, hot = 95.0, critical = 95.0 }\n\
+                 [[panels]]\nkind = \"power\"\nthresholds = { warm = 70.0, hot = 85.0, critical = 80.0 }",
+                "the power thresholds must be warm <= hot <= critical",
+            ),
+            (
+                "[trends]\ntemp = { rate = 0.0, period_secs = 600 }",
+                "the temp trend's rate & period_secs must be more than 0",
+            ),
+            (
+                "[trends]\nfan = { rate = 100.0, period_secs = 0 }",
+                "the fan trend's rate & period_secs must be more than 0",
+            ),
+        ];
+        for (contents, reason) in rejections {
+            assert_rejected(contents, reason);
+        }
+    }
+}
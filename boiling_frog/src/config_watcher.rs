@@ -0,0 +1,121 @@
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use glib::source::unix_fd_add_local;
+use glib::{ControlFlow, IOCondition, SourceId};
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+
+use boiling_frog_dbus::GenericError;
+
+/// Calls `on_change` on the main loop whenever the file at `path` is written, replaced or deleted.
+///
+/// The file's directory is watched rather than the file itself, as editors often save by replacing
+/// the file, and so that a config file can be created after the app starts. Until the directory
+/// exists, its nearest existing ancestor is watched for it to be created.
+pub fn watch(path: &Path, on_change: impl Fn() + 'static) -> Result<SourceId, GenericError> {
+    let directory = path
+        .parent()
+        .ok_or("config file has no directory")?
+        .to_path_buf();
+    let file_name = path
+        .file_name()
+        .ok_or("config path has no file name")?
+        .to_os_string();
+
+    let mut inotify = Inotify::init()?;
+    let mut watched = Watched::nearest(&inotify, &directory)?;
+    if watched.directory != directory {
+        println!(
+            "Watching {} until {} is created",
+            non_empty(&watched.directory).display(),
+            directory.display()
+        );
+    }
+
+    let fd = inotify.as_raw_fd();
+    let mut buffer = [0; 4096];
+    Ok(unix_fd_add_local(fd, IOCondition::IN, move |_, _| {
+        let awaited = watched.awaited(&directory, &file_name);
+        let is_awaited = match inotify.read_events(&mut buffer) {
+            Ok(events) => events.into_iter().any(|event| {
+                event.wd == watched.descriptor && event.name == Some(awaited.as_os_str())
+            }),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => false,
+            Err(e) => {
+                println!("Stopped watching the config file: {e}");
+                return ControlFlow::Break;
+            }
+        };
+
+        if is_awaited && watched.directory == directory {
+            on_change();
+        } else if is_awaited {
+            // The next directory towards the config file was created, so watch the nearest again.
+            let _ = inotify.watches().remove(watched.descriptor.clone());
+            watched = match Watched::nearest(&inotify, &directory) {
+                Ok(watched) => watched,
+                Err(e) => {
+                    println!("Stopped watching the config file: {e}");
+                    return ControlFlow::Break;
+                }
+            };
+            // The file may have been written before its directory was watched.
+            if watched.directory == directory && non_empty(&directory).join(&file_name).exists() {
+                on_change();
+            }
+        }
+
+        ControlFlow::Continue
+    }))
+}
+
+/// The directory being watched, which is the config file's or one of its ancestors.
+struct Watched {
+    directory: PathBuf,
+    descriptor: WatchDescriptor,
+}
+
+impl Watched {
+    /// Watches `directory`, or if it doesn't exist, its nearest ancestor that does.
+    fn nearest(inotify: &Inotify, directory: &Path) -> Result<Watched, GenericError> {
+        let nearest = directory
+            .ancestors()
+            .find(|ancestor| non_empty(ancestor).is_dir())
+            .ok_or("no directory above the config file exists")?;
+        let mask = if nearest == directory {
+            WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::MOVED_FROM | WatchMask::DELETE
+        } else {
+            WatchMask::CREATE | WatchMask::MOVED_TO
+        };
+        Ok(Watched {
+            descriptor: inotify.watches().add(non_empty(nearest), mask)?,
+            directory: nearest.to_path_buf(),
+        })
+    }
+
+    /// The name of the entry in the watched directory whose events matter: the config file in
+    /// its own directory, or else the next directory towards it.
+    fn awaited(&self, directory: &Path, file_name: &OsString) -> OsString {
+        if self.directory == directory {
+            return file_name.clone();
+        }
+        directory
+            .ancestors()
+            .take_while(|ancestor| *ancestor != self.directory)
+            .last()
+            .and_then(Path::file_name)
+            .unwrap_or_default()
+            .to_os_string()
+    }
+}
+
+/// `directory`, where an empty one, e.g. from `--config config.toml`, is the working directory.
+fn non_empty(directory: &Path) -> &Path {
+    if directory.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        directory
+    }
+}
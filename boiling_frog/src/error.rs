@@ -0,0 +1,22 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("Invalid config in {}: {}", .path, .message)]
+pub struct ConfigError {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Error, Debug)]
+#[error("There's nowhere to keep the baseline; set baseline.path in the config file")]
+pub struct NoBaselinePathError {}
+
+#[cfg(not(feature = "sqlite"))]
+#[derive(Error, Debug)]
+#[error("This build can't record readings in a database without SQLite")]
+pub struct NoDatabaseError {}
+
+#[cfg(not(feature = "dbus"))]
+#[derive(Error, Debug)]
+#[error("This build can't send notifications without D-Bus")]
+pub struct NoNotificationsError {}
@@ -0,0 +1,191 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use gtk::cairo::Context;
+use gtk::prelude::*;
+use gtk::{DrawingArea, EventControllerMotion};
+
+use boiling_frog_dbus::{
+    DbusEngine, GenericError, Level, SensorKind, TemperatureScale, Thresholds,
+};
+
+use crate::config::Config;
+use crate::ui_format::{format_value, level_colour};
+
+/// The spans of time a graph can show, with their names.
+pub const WINDOWS: [(&str, Duration); 3] = [
+    ("Last minute", Duration::from_secs(60)),
+    ("Last 5 minutes", Duration::from_secs(5 * 60)),
+    ("Last 30 minutes", Duration::from_secs(30 * 60)),
+];
+
+/// The colour of the line, which shows on light & dark themes.
+const LINE_RGB: (f64, f64, f64) = (0.2, 0.5, 0.9);
+
+/// Plots the recent aggregate readings of one kind over bands showing its thresholds, with the
+/// value under the pointer.
+pub struct Graph {
+    area: DrawingArea,
+}
+
+impl Graph {
+    /// `window` is the span of time shown, which can change while the graph is shown.
+    pub fn new(
+        engine: &Rc<RefCell<DbusEngine>>,
+        config: &Config,
+        kind: SensorKind,
+        window: &Rc<Cell<Duration>>,
+    ) -> Graph {
+        let area = DrawingArea::builder()
+            .content_height(config.graph_height)
+            .hexpand(true)
+            .build();
+
+        let hover = Rc::new(Cell::new(None));
+        let motion = EventControllerMotion::new();
+        let moved_hover = hover.clone();
+        motion.connect_motion(move |controller, x, _| {
+            moved_hover.set(Some(x));
+            if let Some(widget) = controller.widget() {
+                widget.queue_draw();
+            }
+        });
+        let left_hover = hover.clone();
+        motion.connect_leave(move |controller| {
+            left_hover.set(None);
+            if let Some(widget) = controller.widget() {
+                widget.queue_draw();
+            }
+        });
+        area.add_controller(motion);
+
+        let plot = Plot {
+            engine: engine.clone(),
+            hover,
+            kind,
+            temperature_scale: config.temperature_scale,
+            thresholds: config.thresholds(kind).copied(),
+            window: window.clone(),
+        };
+        area.set_draw_func(move |_, context, width, height| {
+            if let Err(e) = plot.draw(context, f64::from(width), f64::from(height)) {
+                println!("Could not draw the {} graph: {e}", kind.name());
+            }
+        });
+
+        Graph { area }
+    }
+
+    pub fn widget(&self) -> &DrawingArea {
+        &self.area
+    }
+
+    /// Redraws the graph with the latest readings.
+    pub fn refresh(&self) {
+        self.area.queue_draw();
+    }
+}
+
+/// Everything a graph's draw function needs.
+struct Plot {
+    engine: Rc<RefCell<DbusEngine>>,
+    /// Where the pointer is across the graph, if it's over it.
+    hover: Rc<Cell<Option<f64>>>,
+    kind: SensorKind,
+    temperature_scale: TemperatureScale,
+    thresholds: Option<Thresholds>,
+    window: Rc<Cell<Duration>>,
+}
+
+impl Plot {
+    fn draw(&self, context: &Context, width: f64, height: f64) -> Result<(), GenericError> {
+        let now = Instant::now();
+        let window = self.window.get();
+        let from = now.checked_sub(window).unwrap_or(now);
+        let samples = self.engine.borrow().history(self.kind, from, now)?;
+        if samples.is_empty() {
+            return Ok(());
+        }
+
+        // The range of values shown, with some space above & below the line.
+        let (low, high) = samples
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), sample| {
+                (low.min(sample.min), high.max(sample.max))
+            });
+        let padding = ((high - low) * 0.1).max(1.0);
+        let (low, high) = (low - padding, high + padding);
+        let x = |time: Instant| {
+            let age = now.saturating_duration_since(time).as_secs_f64();
+            (width * (1.0 - age / window.as_secs_f64())).max(0.0)
+        };
+        let y = |value: f64| height * (high - value) / (high - low);
+
+        if let Some(thresholds) = &self.thresholds {
+            let bands = [
+                (Level::Warm, thresholds.warm, thresholds.hot),
+                (Level::Hot, thresholds.hot, thresholds.critical),
+                (Level::Critical, thresholds.critical, f64::INFINITY),
+            ];
+            for (level, start, end) in bands {
+                let (top, bottom) = (y(end.min(high)), y(start.max(low)));
+                if let (Some((red, green, blue)), true) = (level_colour(level), bottom > top) {
+                    context.set_source_rgba(
+                        f64::from(red) / 255.0,
+                        f64::from(green) / 255.0,
+                        f64::from(blue) / 255.0,
+                        0.2,
+                    );
+                    context.rectangle(0.0, top, width, bottom - top);
+                    context.fill()?;
+                }
+            }
+        }
+
+        // Each reading holds until the next, & the latest until now.
+        let (red, green, blue) = LINE_RGB;
+        context.set_source_rgb(red, green, blue);
+        context.set_line_width(2.0);
+        context.move_to(x(samples[0].time), y(samples[0].value));
+        for pair in samples.windows(2) {
+            let (previous, sample) = (&pair[0], &pair[1]);
+            context.line_to(x(sample.time), y(previous.value));
+            context.line_to(x(sample.time), y(sample.value));
+        }
+        if let Some(latest) = samples.last() {
+            context.line_to(width, y(latest.value));
+        }
+        context.stroke()?;
+
+        if let Some(hover_x) = self.hover.get() {
+            let time = now
+                .checked_sub(window.mul_f64((1.0 - hover_x / width).clamp(0.0, 1.0)))
+                .unwrap_or(now);
+            let sample = samples
+                .iter()
+                .rev()
+                .find(|sample| sample.time <= time)
+                .unwrap_or(&samples[0]);
+
+            context.set_line_width(1.0);
+            context.move_to(hover_x, 0.0);
+            context.line_to(hover_x, height);
+            context.stroke()?;
+
+            let label = format_value(sample.value, &self.kind.units(), self.temperature_scale);
+            context.set_font_size(12.0);
+            let extents = context.text_extents(&label)?;
+            // Kept inside the graph, on whichever side of the pointer has room.
+            let label_x = if hover_x + 4.0 + extents.width() > width {
+                hover_x - 4.0 - extents.width()
+            } else {
+                hover_x + 4.0
+            };
+            context.move_to(label_x, extents.height() + 4.0);
+            context.show_text(&label)?;
+        }
+
+        Ok(())
+    }
+}
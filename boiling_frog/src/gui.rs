@@ -0,0 +1,775 @@
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::process::{ExitCode, Termination};
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use glib::source::timeout_add_local;
+use glib::ControlFlow::Continue;
+use glib::SourceId;
+use glib::VariantTy;
+use gtk::gdk::Display;
+use gtk::gio::{
+    ApplicationCommandLine, ApplicationFlags, Notification, NotificationPriority, SimpleAction,
+};
+use gtk::pango::EllipsizeMode;
+use gtk::prelude::*;
+use gtk::Orientation::{Horizontal, Vertical};
+use gtk::{
+    Align, Application, ApplicationWindow, Box, CssProvider, DropDown, Frame, Label, Orientation,
+    StringList, ToggleButton, Widget, STYLE_PROVIDER_PRIORITY_APPLICATION,
+};
+
+use boiling_frog_dbus::{
+    Alert, AlertSource, DbusEngine, GenericError, Level, Reading, ReadingStatus, SensorKind,
+    SensorSnapshot, TemperatureScale, Thresholds, SNOOZE,
+};
+
+use crate::cli::Cli;
+use crate::config::{recording_path, Config, PanelConfig};
+use crate::error::ConfigError;
+use crate::graph::{Graph, WINDOWS};
+use crate::recorder::Recorder;
+use crate::ui_format::{alert_text, drift_text, level_colour, sensor_line, trend_text};
+use crate::{build_engine, config_watcher, make_value_units_string, set_margins};
+
+const APP_ID: &str = "com.robwilliamson.boiling_frog";
+const TITLE: &str = "Boiling Frog";
+/// How often each panel's trend is worked out again, as fitting it is far slower than reading.
+const TREND_REFRESH: Duration = Duration::from_secs(2);
+
+/// Shows the readings in a window until it's closed.
+pub fn run() -> ExitCode {
+    // Create a new application. If it's already running, the arguments are passed to that
+    // instance's "command-line" signal instead.
+    let app = Application::builder()
+        .application_id(APP_ID)
+        .flags(ApplicationFlags::HANDLES_COMMAND_LINE)
+        .build();
+
+    app.connect_startup(|_| load_css());
+    let running: Rc<RefCell<Option<Rc<RefCell<LiveUi>>>>> = Rc::default();
+    app.connect_command_line(move |app, command_line| {
+        handle_command_line(app, command_line, &running)
+    });
+
+    // Run the application
+    app.run().report()
+}
+
+/// Builds the window, or when the app is already `running`, applies the arguments to its window &
+/// shows it rather than opening another.
+fn handle_command_line(
+    app: &Application,
+    command_line: &ApplicationCommandLine,
+    running: &Rc<RefCell<Option<Rc<RefCell<LiveUi>>>>>,
+) -> i32 {
+    // Each instance has already checked its own arguments in `main`.
+    let cli = match Cli::try_parse_from(command_line.arguments()) {
+        Ok(cli) => cli,
+        Err(e) => {
+            println!("{}", e);
+            return glib::ExitCode::FAILURE.into();
+        }
+    };
+
+    let live_ui = running.borrow().clone();
+    match (app.active_window(), live_ui) {
+        (Some(window), Some(live_ui)) => {
+            LiveUi::relaunch(&live_ui, &cli);
+            window.present();
+        }
+        // The error window is replaced, in case the new arguments fix the error.
+        (Some(window), None) => {
+            *running.borrow_mut() = build_ui(app, &cli);
+            window.close();
+        }
+        (None, _) => *running.borrow_mut() = build_ui(app, &cli),
+    }
+
+    glib::ExitCode::SUCCESS.into()
+}
+
+/// Colours each panel by its reading's [`Level`], which is one of its CSS classes, & makes it
+/// blink while critical if it has the `blink` class.
+fn load_css() {
+    let mut css = String::from(
+        "@keyframes blink { 50% { opacity: 0.3; } }\n\
+        frame.critical.blink { animation: blink 1s ease-in-out infinite; }\n",
+    );
+    for level in Level::ALL {
+        if let Some((red, green, blue)) = level_colour(level) {
+            let colour = format!("#{red:02x}{green:02x}{blue:02x}");
+            css.push_str(&format!(
+                "frame.{name} {{ border: 2px solid {colour}; }}\n\
+                frame.{name} label {{ color: {colour}; }}\n",
+                name = level.name()
+            ));
+        }
+    }
+
+    let provider = CssProvider::new();
+    provider.load_from_data(&css);
+    match Display::default() {
+        Some(display) => gtk::style_context_add_provider_for_display(
+            &display,
+            &provider,
+            STYLE_PROVIDER_PRIORITY_APPLICATION,
+        ),
+        None => println!("Can't colour the panels without a display"),
+    }
+}
+
+/// Shows the window, returning its contents unless it only shows an error.
+fn build_ui(app: &Application, cli: &Cli) -> Option<Rc<RefCell<LiveUi>>> {
+    let config = match cli.load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}", e);
+            build_error_path_ui(app, &Config::default(), &e).present();
+            return None;
+        }
+    };
+
+    // Present window
+    match build_happy_path_ui(app, cli, &config) {
+        Ok(live_ui) => {
+            live_ui.borrow().window.present();
+            Some(live_ui)
+        }
+        Err(e) => {
+            println!("{}", e);
+            build_error_path_ui(app, &config, &e).present();
+            None
+        }
+    }
+}
+
+fn build_error_path_ui(
+    app: &Application,
+    config: &Config,
+    error: &GenericError,
+) -> ApplicationWindow {
+    // A broken config file has nothing to do with where the sensor data comes from.
+    let is_sensor_error = error.downcast_ref::<ConfigError>().is_none();
+    let grid = set_margins!(Box::builder(), config.margin)
+        .orientation(Orientation::Vertical)
+        .build();
+    let label = set_margins!(Label::builder(), config.margin)
+        .label(
+            "Could not receive device thermal data from Hardware Sensors Indicator or \
+            /sys/class/hwmon. Is Hardware Sensors Indicator installed?",
+        )
+        .selectable(true)
+        .visible(is_sensor_error)
+        .build();
+    let link = set_margins!(Label::builder(), config.margin)
+        .use_markup(true)
+        .label("<a href=\"https://github.com/alexmurray/indicator-sensors\">Hardware Sensors Indicator</a>")
+        .selectable(true)
+        .visible(is_sensor_error)
+        .build();
+
+    let error_label = set_margins!(Label::builder(), config.margin)
+        .label(error.to_string())
+        .selectable(true)
+        .wrap(true)
+        .build();
+
+    grid.append(&label);
+    grid.append(&link);
+    grid.append(&error_label);
+
+    make_window(app, &grid)
+}
+
+fn build_happy_path_ui(
+    app: &Application,
+    cli: &Cli,
+    config: &Config,
+) -> Result<Rc<RefCell<LiveUi>>, GenericError> {
+    let (alert_sender, alerts) = channel();
+    let engine = build_engine(config)?;
+    subscribe_alerts(&engine, &alert_sender)?;
+    let window = make_window(app, &Box::builder().build());
+    let recorder = match &cli.record {
+        Some(path) => start_recording(&engine, path, config.temperature_scale),
+        None => None,
+    };
+    let live_ui = Rc::new(RefCell::new(LiveUi {
+        alert_sender,
+        alerts: Rc::new(alerts),
+        cli: cli.clone(),
+        config: config.clone(),
+        config_watch: None,
+        engine: Rc::new(RefCell::new(engine)),
+        graph_window: Rc::new(Cell::new(WINDOWS[1].1)),
+        recorder: Rc::new(RefCell::new(recorder)),
+        timer: None,
+        window: window.clone(),
+    }));
+    live_ui.borrow_mut().rebuild(None);
+
+    // Snoozes the reading whose notification's button was pressed.
+    let snooze = SimpleAction::new("snooze", Some(VariantTy::STRING));
+    let snoozed_engine = live_ui.borrow().engine.clone();
+    snooze.connect_activate(move |_, parameter| {
+        let Some(source) = parameter
+            .and_then(|parameter| parameter.str())
+            .and_then(notification_source)
+        else {
+            return;
+        };
+        if let Err(e) = snoozed_engine.borrow().snooze_source(&source, SNOOZE) {
+            println!("Can't snooze notifications: {e}");
+        }
+    });
+    app.add_action(&snooze);
+
+    if config.notifications.enabled {
+        if let Err(e) = notify_drift(app, &live_ui.borrow().engine.borrow(), config) {
+            println!("Can't compare with the baseline: {e}");
+        }
+    }
+
+    LiveUi::watch_config(&live_ui);
+
+    Ok(live_ui)
+}
+
+/// The window's contents & the engine behind them, kept in step with the config file.
+struct LiveUi {
+    /// Passes alerts from the engine's threads to the main loop.
+    alert_sender: Sender<Alert>,
+    alerts: Rc<Receiver<Alert>>,
+    /// Overrides the config file whenever it's reloaded.
+    cli: Cli,
+    config: Config,
+    /// Reloads the config file when it changes.
+    config_watch: Option<SourceId>,
+    engine: Rc<RefCell<DbusEngine>>,
+    /// The span of time the graphs show, which is kept when the config is reloaded.
+    graph_window: Rc<Cell<Duration>>,
+    /// Records every reading to a file while the "Record" button is pressed.
+    recorder: Rc<RefCell<Option<Recorder>>>,
+    timer: Option<SourceId>,
+    window: ApplicationWindow,
+}
+
+impl LiveUi {
+    /// Applies the arguments the app was launched with again while running, on top of the ones
+    /// it was first launched with.
+    fn relaunch(live_ui: &Rc<RefCell<LiveUi>>, cli: &Cli) {
+        let cli = live_ui.borrow().cli.overridden_by(cli);
+        let is_new_config = cli.config_path() != live_ui.borrow().cli.config_path();
+        let is_new_recording = cli.record != live_ui.borrow().cli.record;
+        live_ui.borrow_mut().cli = cli;
+
+        if is_new_config {
+            LiveUi::watch_config(live_ui);
+        }
+        let mut ui = live_ui.borrow_mut();
+        if let Some(path) = ui.cli.record.clone().filter(|_| is_new_recording) {
+            let engine = ui.engine.borrow();
+            let mut recorder = ui.recorder.borrow_mut();
+            if let Some(mut recording) = recorder.take() {
+                if let Err(e) = recording.detach(&engine) {
+                    println!("Can't stop recording: {e}");
+                }
+            }
+            *recorder = start_recording(&engine, &path, ui.config.temperature_scale);
+        }
+        // Rebuilds the window with the new arguments' config & recording.
+        ui.reload();
+    }
+
+    /// Reloads the config whenever the file it's read from changes, instead of any file watched
+    /// before.
+    fn watch_config(live_ui: &Rc<RefCell<LiveUi>>) {
+        if let Some(watch) = live_ui.borrow_mut().config_watch.take() {
+            watch.remove();
+        }
+        let Some(path) = live_ui.borrow().cli.config_path() else {
+            return;
+        };
+        let watched_ui = live_ui.clone();
+        match config_watcher::watch(&path, move || watched_ui.borrow_mut().reload()) {
+            Ok(watch) => live_ui.borrow_mut().config_watch = Some(watch),
+            Err(e) => println!("Can't watch {} for changes: {e}", path.display()),
+        }
+    }
+
+    /// Applies the config file's current contents. If they're invalid, the current config stays in
+    /// use & a banner says why.
+    fn reload(&mut self) {
+        if let Err(e) = self.cli.load_config().and_then(|config| self.apply(config)) {
+            println!("{}", e);
+            self.rebuild(Some(&e.to_string()));
+        }
+    }
+
+    fn apply(&mut self, config: Config) -> Result<(), GenericError> {
+        if config.dbus != self.config.dbus
+            || config.baseline != self.config.baseline
+            || config.database != self.config.database
+        {
+            // The old engine shuts down as it's replaced, but its baseline has to be saved
+            // before the new one reads it.
+            self.engine.borrow().save_baseline()?;
+            let engine = build_engine(&config)?;
+            subscribe_alerts(&engine, &self.alert_sender)?;
+            if let Some(recorder) = self.recorder.borrow_mut().as_mut() {
+                recorder.attach(&engine)?;
+            }
+            *self.engine.borrow_mut() = engine;
+        } else {
+            let mut engine = self.engine.borrow_mut();
+            engine.set_filter(&config.filters)?;
+            engine.set_stale_after(config.stale_after());
+            engine.set_thresholds(&config.all_thresholds())?;
+            engine.set_trends(&config.trend_settings())?;
+            engine.set_alert_settings(&config.notifications.alert_settings())?;
+            for panel in &config.panels {
+                engine.set_aggregation(panel.kind, panel.aggregation.clone())?;
+            }
+        }
+
+        self.config = config;
+        self.rebuild(None);
+        Ok(())
+    }
+
+    /// Replaces the window's contents & restarts the refresh timer to match the config, with a
+    /// banner showing `error` if there is one.
+    fn rebuild(&mut self, error: Option<&str>) {
+        if let Some(timer) = self.timer.take() {
+            timer.remove();
+        }
+
+        let config = &self.config;
+        let panels: Vec<Panel> = config
+            .panels
+            .iter()
+            .map(|panel| Panel::new(&self.engine, config, panel, &self.graph_window))
+            .collect();
+
+        let metrics_grid = set_margins!(Box::builder(), config.margin)
+            .orientation(Horizontal)
+            .build();
+
+        for panel in &panels {
+            metrics_grid.append(&panel.frame);
+        }
+
+        let names: Vec<&str> = WINDOWS.iter().map(|(name, _)| *name).collect();
+        let window_selector = set_margins!(DropDown::builder(), config.margin)
+            .model(&StringList::new(&names))
+            .selected(
+                WINDOWS
+                    .iter()
+                    .position(|(_, window)| *window == self.graph_window.get())
+                    .unwrap_or(1) as u32,
+            )
+            .halign(Align::End)
+            .visible(config.graph_height > 0)
+            .build();
+        let graph_window = self.graph_window.clone();
+        window_selector.connect_selected_notify(move |selector| {
+            if let Some((_, window)) = WINDOWS.get(selector.selected() as usize) {
+                graph_window.set(*window);
+            }
+        });
+
+        let recording_label = set_margins!(Label::builder(), config.margin)
+            .css_classes(["dim-label"])
+            .ellipsize(EllipsizeMode::Start)
+            .hexpand(true)
+            .xalign(0.0)
+            .build();
+        let record_button = set_margins!(ToggleButton::builder(), config.margin)
+            .label("Record")
+            .tooltip_text("Appends every reading to a file")
+            .valign(Align::Center)
+            .build();
+        let shown_path = recording_label.clone();
+        let show_recording = move |recorder: &Option<Recorder>| {
+            let path = recorder
+                .as_ref()
+                .map(|recorder| format!("Recording to {}", recorder.path().display()));
+            shown_path.set_label(path.as_deref().unwrap_or_default());
+        };
+        show_recording(&self.recorder.borrow());
+        record_button.set_active(self.recorder.borrow().is_some());
+        let recorder = self.recorder.clone();
+        let recorded_engine = self.engine.clone();
+        let record_path = self.cli.record.clone();
+        let temperature_scale = config.temperature_scale;
+        record_button.connect_toggled(move |button| {
+            let mut recorder = recorder.borrow_mut();
+            if button.is_active() == recorder.is_some() {
+                return;
+            }
+            let engine = recorded_engine.borrow();
+            *recorder = match recorder.take() {
+                Some(mut recording) => {
+                    if let Err(e) = recording.detach(&engine) {
+                        println!("Can't stop recording: {e}");
+                    }
+                    None
+                }
+                None => record_path
+                    .clone()
+                    .or_else(recording_path)
+                    .and_then(|path| start_recording(&engine, &path, temperature_scale)),
+            };
+            show_recording(&recorder);
+            let is_recording = recorder.is_some();
+            drop(recorder);
+            // Pops back up if recording couldn't start.
+            button.set_active(is_recording);
+        });
+
+        let controls = Box::builder().orientation(Horizontal).build();
+        controls.append(&record_button);
+        controls.append(&recording_label);
+        controls.append(&window_selector);
+
+        let banner = set_margins!(Label::builder(), config.margin)
+            .label(format!(
+                "The config file was not applied: {}",
+                error.unwrap_or_default()
+            ))
+            .css_classes(["error"])
+            .selectable(true)
+            .wrap(true)
+            .visible(error.is_some())
+            .build();
+
+        // Shown until there's something to read, e.g. while Indicator Sensors is (re)starting.
+        let waiting_label = set_margins!(Label::builder(), config.margin)
+            .use_markup(true)
+            .label(
+                "Waiting for device thermal data from \
+                <a href=\"https://github.com/alexmurray/indicator-sensors\">Hardware Sensors Indicator</a>\
+                …",
+            )
+            .visible(self.engine.borrow().is_waiting().unwrap_or(true))
+            .build();
+
+        let gtk_box = Box::builder().orientation(Vertical).build();
+        gtk_box.append(&banner);
+        gtk_box.append(&waiting_label);
+        gtk_box.append(&metrics_grid);
+        gtk_box.append(&controls);
+        self.window.set_child(Some(&gtk_box));
+
+        // Poll the engine because GTK is not thread-safe.
+        let alerts = self.alerts.clone();
+        let app = self.window.application();
+        let engine = self.engine.clone();
+        let font_size = config.font_size;
+        let notifications_enabled = config.notifications.enabled;
+        let temperature_scale = config.temperature_scale;
+        self.timer = Some(timeout_add_local(config.update_rate(), move || {
+            for alert in alerts.try_iter() {
+                if let (Some(app), true) = (&app, notifications_enabled) {
+                    send_notification(app, &alert, temperature_scale);
+                }
+            }
+            let engine = engine.borrow();
+            waiting_label.set_visible(engine.is_waiting().unwrap_or(true));
+            let snapshots = engine.sensors();
+            for panel in &panels {
+                panel.refresh(&engine, snapshots.as_deref(), font_size, temperature_scale);
+            }
+            Continue
+        }));
+    }
+}
+
+/// Starts recording every reading `engine` receives to the file at `path`, or says why it can't.
+fn start_recording(
+    engine: &DbusEngine,
+    path: &Path,
+    temperature_scale: TemperatureScale,
+) -> Option<Recorder> {
+    let recorder = Recorder::new(path, temperature_scale).and_then(|mut recorder| {
+        recorder.attach(engine)?;
+        Ok(recorder)
+    });
+    match recorder {
+        Ok(recorder) => Some(recorder),
+        Err(e) => {
+            println!("Can't record to {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Sends each of `engine`'s alerts to `sender`, because GTK can only be used from the main
+/// thread.
+fn subscribe_alerts(engine: &DbusEngine, sender: &Sender<Alert>) -> Result<(), GenericError> {
+    let sender = sender.clone();
+    // `Box` is GTK's here.
+    engine.subscribe_alerts(&Arc::new(Mutex::new(std::boxed::Box::new(
+        move |alert: Alert| {
+            // The receiver only goes away as the app shuts down.
+            let _ = sender.send(alert);
+            Ok(())
+        },
+    ))))?;
+    Ok(())
+}
+
+/// Shows `alert` as a desktop notification, replacing any earlier one about the same reading.
+fn send_notification(app: &Application, alert: &Alert, temperature_scale: TemperatureScale) {
+    let (summary, body) = alert_text(alert, temperature_scale);
+    let notification = Notification::new(&summary);
+    notification.set_body(Some(&body));
+    notification.set_priority(if alert.level() == Level::Critical {
+        NotificationPriority::Urgent
+    } else {
+        NotificationPriority::High
+    });
+    let id = notification_id(&alert.source);
+    notification.add_button_with_target_value(
+        "Snooze 10 min",
+        "app.snooze",
+        Some(&id.to_variant()),
+    );
+    app.send_notification(Some(&id), &notification);
+}
+
+/// Identifies the notification about the reading from `source`.
+fn notification_id(source: &AlertSource) -> String {
+    match source {
+        AlertSource::Aggregate(kind) => format!("aggregate-{}", kind.name()),
+        AlertSource::Sensor(id) => format!("sensor-{id}"),
+    }
+}
+
+/// The reading a [`notification_id`] is about.
+fn notification_source(id: &str) -> Option<AlertSource> {
+    if let Some(id) = id.strip_prefix("sensor-") {
+        return Some(AlertSource::Sensor(id.to_string()));
+    }
+    let name = id.strip_prefix("aggregate-")?;
+    SensorKind::ALL
+        .into_iter()
+        .find(|kind| kind.name() == name)
+        .map(AlertSource::Aggregate)
+}
+
+/// Notifies the user if the machine runs noticeably hotter, or its fans spin noticeably faster,
+/// than it used to under the same conditions, going by the baseline `engine` has read.
+fn notify_drift(
+    app: &Application,
+    engine: &DbusEngine,
+    config: &Config,
+) -> Result<(), GenericError> {
+    let Some(baseline) = engine.baseline()? else {
+        return Ok(());
+    };
+    let rises: Vec<String> = config
+        .baseline
+        .compare(&baseline)
+        .iter()
+        .filter(|drift| config.baseline.is_worrying(drift))
+        .map(|drift| drift_text(drift, config.temperature_scale))
+        .collect();
+    if rises.is_empty() {
+        return Ok(());
+    }
+
+    let notification = Notification::new("Running hotter than it used to");
+    notification.set_body(Some(&format!(
+        "Compared with {} days ago: {}. Cleaning the fans or replacing the thermal paste may help.",
+        config.baseline.age_days,
+        rises.join(", ")
+    )));
+    app.send_notification(Some("baseline-drift"), &notification);
+    Ok(())
+}
+
+/// Shows the aggregate reading of one kind of sensor.
+struct Panel {
+    config: PanelConfig,
+    frame: Frame,
+    /// `None` if graphs are turned off.
+    graph: Option<Graph>,
+    thresholds: Option<Thresholds>,
+    /// Shows how fast the reading is rising while it's rising steadily.
+    trend_label: Label,
+    /// When the trend was last worked out.
+    trend_refreshed: Cell<Option<Instant>>,
+    /// How fast the reading has to rise to count as rising steadily, if it can.
+    trend_rate: Option<f64>,
+    value_label: Label,
+}
+
+impl Panel {
+    fn new(
+        engine_ref: &Rc<RefCell<DbusEngine>>,
+        config: &Config,
+        panel_config: &PanelConfig,
+        graph_window: &Rc<Cell<Duration>>,
+    ) -> Panel {
+        let engine = engine_ref.borrow();
+        let title_label = set_margins!(Label::builder(), config.margin)
+            .label(panel_config.title())
+            .build();
+
+        // https://docs.gtk.org/Pango/pango_markup.html
+        let value_label = set_margins!(Label::builder(), config.margin)
+            .use_markup(true)
+            .label(make_value_units_string!(
+                &engine.reading(panel_config.kind),
+                config.font_size,
+                config.temperature_scale
+            ))
+            .build();
+
+        // https://docs.gtk.org/gtk4/visual_index.html
+        let grid = set_margins!(Box::builder(), config.margin)
+            .orientation(Vertical)
+            .build();
+
+        let trend_label = Label::builder()
+            .css_classes(["dim-label"])
+            .visible(false)
+            .build();
+
+        grid.append(&title_label);
+        grid.append(&value_label);
+        grid.append(&trend_label);
+
+        let graph = (config.graph_height > 0)
+            .then(|| Graph::new(engine_ref, config, panel_config.kind, graph_window));
+        if let Some(graph) = &graph {
+            grid.append(graph.widget());
+        }
+
+        let frame = set_margins!(Frame::builder(), config.margin)
+            .child(&grid)
+            .visible(panel_config.is_wanted(&engine.sensors().unwrap_or_default()))
+            .build();
+        if config.blink_critical {
+            frame.add_css_class("blink");
+        }
+
+        let panel = Panel {
+            config: panel_config.clone(),
+            frame,
+            graph,
+            thresholds: config.thresholds(panel_config.kind).copied(),
+            trend_label,
+            trend_refreshed: Cell::new(None),
+            trend_rate: config
+                .trends
+                .get(&panel_config.kind)
+                .map(|trend| trend.rate),
+            value_label,
+        };
+        panel.set_level(&engine.reading(panel_config.kind));
+        panel
+    }
+
+    /// Shows the panel's latest reading, given `snapshots` of every sensor, fetched once per tick.
+    fn refresh(
+        &self,
+        engine: &DbusEngine,
+        snapshots: Result<&[SensorSnapshot], &GenericError>,
+        font_size: f64,
+        temperature_scale: TemperatureScale,
+    ) {
+        let reading = engine.reading(self.config.kind);
+        self.frame
+            .set_visible(self.config.is_wanted(snapshots.unwrap_or_default()));
+        self.value_label.set_label(&make_value_units_string!(
+            &reading,
+            font_size,
+            temperature_scale
+        ));
+        self.set_level(&reading);
+        self.set_trend(engine, temperature_scale);
+        let tooltip = sensor_tooltip(snapshots, self.config.kind, temperature_scale);
+        if self.frame.tooltip_text().as_deref() != Some(tooltip.as_str()) {
+            self.frame.set_tooltip_text(Some(&tooltip));
+        }
+        if let Some(graph) = &self.graph {
+            graph.refresh();
+        }
+    }
+
+    fn set_trend(&self, engine: &DbusEngine, temperature_scale: TemperatureScale) {
+        let now = Instant::now();
+        if self.trend_rate.is_none()
+            || self
+                .trend_refreshed
+                .get()
+                .is_some_and(|refreshed| now.saturating_duration_since(refreshed) < TREND_REFRESH)
+        {
+            return;
+        }
+        self.trend_refreshed.set(Some(now));
+        let trend = engine
+            .trend(self.config.kind)
+            .ok()
+            .flatten()
+            .filter(|trend| self.trend_rate.is_some_and(|rate| trend.rate >= rate));
+        self.trend_label.set_visible(trend.is_some());
+        if let Some(trend) = trend {
+            self.trend_label.set_label(&trend_text(
+                &trend,
+                &self.config.kind.units(),
+                temperature_scale,
+            ));
+        }
+    }
+
+    /// Sets the frame's CSS class to the [`Level::name`] of `reading`, which is normal until
+    /// there is one.
+    fn set_level(&self, reading: &Result<Reading, GenericError>) {
+        let level = match (reading, &self.thresholds) {
+            (Ok(reading), Some(thresholds)) if reading.status != ReadingStatus::NeverReceived => {
+                thresholds.level(reading.value)
+            }
+            _ => Level::Normal,
+        };
+        for other in Level::ALL {
+            if other != level {
+                self.frame.remove_css_class(other.name());
+            }
+        }
+        self.frame.add_css_class(level.name());
+    }
+}
+
+/// Lists every `kind` sensor with its latest reading, one per line.
+fn sensor_tooltip(
+    snapshots: Result<&[SensorSnapshot], &GenericError>,
+    kind: SensorKind,
+    temperature_scale: TemperatureScale,
+) -> String {
+    snapshots
+        .map(|snapshots| {
+            snapshots
+                .iter()
+                .filter(|snapshot| snapshot.kind == kind)
+                .map(|snapshot| sensor_line(snapshot, temperature_scale))
+                .collect::<Vec<String>>()
+                .join("\n")
+        })
+        .unwrap_or_else(|e| e.to_string())
+}
+
+fn make_window(app: &Application, child: &impl IsA<Widget>) -> ApplicationWindow {
+    ApplicationWindow::builder()
+        .application(app)
+        .title(TITLE)
+        .child(child)
+        .build()
+}
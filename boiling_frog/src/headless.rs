@@ -0,0 +1,277 @@
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime};
+
+use boiling_frog_dbus::{
+    Baseline, DbusEngine, Drift, GenericError, Reading, ReadingStatus, SensorSnapshot,
+};
+
+use crate::bar;
+use crate::cli::Command;
+use crate::config::Config;
+use crate::error::NoBaselinePathError;
+#[cfg(not(feature = "dbus"))]
+use crate::error::NoNotificationsError;
+use crate::recorder::Recorder;
+#[cfg(feature = "dbus")]
+use crate::ui_format::alert_text;
+use crate::ui_format::{condition_text, drift_text, format_change, format_value};
+use crate::watch;
+
+/// The name notifications are sent under.
+#[cfg(feature = "dbus")]
+const APP_NAME: &str = "Boiling Frog";
+/// How long to wait for every sensor to send a first reading before printing anyway.
+const FIRST_READING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Runs one of the commands that print to the terminal instead of showing a window, recording
+/// the readings meanwhile to `record` if it's set.
+pub fn run(command: Command, config: &Config, record: Option<&Path>) -> ExitCode {
+    let result = match command {
+        // Reads the saved baseline, so it needs no sensors.
+        Command::Drift => drift(config),
+        command => crate::build_engine(config).and_then(|mut engine| {
+            // Held until the command finishes, so the recording lasts as long as the engine.
+            let _recorder = match record {
+                Some(path) => {
+                    let mut recorder = Recorder::new(path, config.temperature_scale)?;
+                    recorder.attach(&engine)?;
+                    Some(recorder)
+                }
+                None => None,
+            };
+            match command {
+                Command::Gui | Command::Drift => Ok(()),
+                Command::List => list(&engine),
+                Command::Watch(args) => {
+                    if args.notify {
+                        notify_desktop(&mut engine, config)?;
+                    }
+                    watch::run(&engine, config, args)
+                }
+                Command::Bar(args) => {
+                    if args.notify {
+                        notify_desktop(&mut engine, config)?;
+                    }
+                    bar::run(&engine, config, &args)
+                }
+                Command::Dump => dump(&engine, config),
+            }
+        }),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        // The output was piped into something that has stopped reading, e.g. `head`.
+        Err(e) if is_broken_pipe(&e) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(feature = "dbus")]
+fn notify_desktop(engine: &mut DbusEngine, config: &Config) -> Result<(), GenericError> {
+    let temperature_scale = config.temperature_scale;
+    engine.notify_desktop(
+        APP_NAME,
+        Box::new(move |alert| alert_text(alert, temperature_scale)),
+    )
+}
+
+#[cfg(not(feature = "dbus"))]
+fn notify_desktop(_engine: &mut DbusEngine, _config: &Config) -> Result<(), GenericError> {
+    Err(Box::new(NoNotificationsError {}))
+}
+
+fn is_broken_pipe(error: &GenericError) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == ErrorKind::BrokenPipe)
+}
+
+fn list(engine: &DbusEngine) -> Result<(), GenericError> {
+    wait_for_first_readings(engine)?;
+    let mut rows = vec![header(&["KIND", "UNITS", "LABEL", "ID"])];
+    rows.extend(engine.sensors()?.iter().map(|snapshot| {
+        vec![
+            snapshot.kind.name().to_string(),
+            snapshot.units.symbol().to_string(),
+            snapshot.label.clone(),
+            snapshot.id.clone(),
+        ]
+    }));
+    print_table(&rows);
+
+    Ok(())
+}
+
+fn dump(engine: &DbusEngine, config: &Config) -> Result<(), GenericError> {
+    wait_for_first_readings(engine)?;
+
+    let snapshots = engine.sensors()?;
+    let mut rows = vec![header(&["PANEL", "VALUE", "SENSOR"])];
+    for panel in config
+        .panels
+        .iter()
+        .filter(|panel| panel.is_wanted(&snapshots))
+    {
+        let reading = engine.reading(panel.kind)?;
+        rows.push(vec![
+            panel.title(),
+            reading_value(&reading, config),
+            reading.label,
+        ]);
+    }
+    print_table(&rows);
+    println!();
+
+    let mut rows = vec![header(&["LABEL", "KIND", "VALUE", "STATUS", "ID"])];
+    rows.extend(snapshots.iter().map(|snapshot| {
+        vec![
+            snapshot.label.clone(),
+            snapshot.kind.name().to_string(),
+            snapshot_value(snapshot, config),
+            status_name(snapshot.status).to_string(),
+            snapshot.id.clone(),
+        ]
+    }));
+    print_table(&rows);
+
+    Ok(())
+}
+
+fn drift(config: &Config) -> Result<(), GenericError> {
+    let path = config
+        .baseline
+        .path()
+        .ok_or_else(|| Box::new(NoBaselinePathError {}))?;
+    let baseline = Baseline::load(&path)?;
+    let Some(since) = baseline.since() else {
+        println!("No baseline has been recorded in {} yet.", path.display());
+        return Ok(());
+    };
+    let days = SystemTime::now()
+        .duration_since(since)
+        .map(|recorded| recorded.as_secs() / (24 * 60 * 60) + 1)
+        .unwrap_or(1);
+    println!(
+        "The baseline in {} goes back {days} day{}.",
+        path.display(),
+        if days == 1 { "" } else { "s" }
+    );
+
+    let drifts = config.baseline.compare(&baseline);
+    if drifts.is_empty() {
+        println!(
+            "There aren't enough readings from {} days ago to compare with yet.",
+            config.baseline.age_days
+        );
+        return Ok(());
+    }
+    println!();
+    let mut rows = vec![header(&["KIND", "CONDITION", "THEN", "NOW", "CHANGE"])];
+    rows.extend(drifts.iter().map(|drift| {
+        let units = drift.kind.units();
+        vec![
+            drift.kind.name().to_string(),
+            condition_text(&drift.condition),
+            format_value(drift.then, &units, config.temperature_scale),
+            format_value(drift.now, &units, config.temperature_scale),
+            format_change(drift.change(), &units, config.temperature_scale),
+        ]
+    }));
+    print_table(&rows);
+    println!();
+
+    let worrying: Vec<&Drift> = drifts
+        .iter()
+        .filter(|drift| config.baseline.is_worrying(drift))
+        .collect();
+    if worrying.is_empty() {
+        println!(
+            "Nothing has risen much since {} days ago.",
+            config.baseline.age_days
+        );
+    }
+    for drift in worrying {
+        println!(
+            "{}, compared with {} days ago.",
+            drift_text(drift, config.temperature_scale),
+            config.baseline.age_days
+        );
+    }
+
+    Ok(())
+}
+
+/// Waits until every sensor has been read, as backends report their sensors before their values.
+pub(crate) fn wait_for_first_readings(engine: &DbusEngine) -> Result<(), GenericError> {
+    let start = Instant::now();
+    while start.elapsed() < FIRST_READING_TIMEOUT {
+        let is_read = !engine.is_waiting()?
+            && engine
+                .sensors()?
+                .iter()
+                .all(|snapshot| snapshot.value.is_some());
+        if is_read {
+            break;
+        }
+        sleep(Duration::from_millis(50));
+    }
+
+    Ok(())
+}
+
+fn reading_value(reading: &Reading, config: &Config) -> String {
+    match reading.status {
+        ReadingStatus::NeverReceived => status_name(reading.status).to_string(),
+        _ => format_value(reading.value, &reading.units, config.temperature_scale),
+    }
+}
+
+fn snapshot_value(snapshot: &SensorSnapshot, config: &Config) -> String {
+    snapshot
+        .value
+        .map(|value| format_value(value, &snapshot.units, config.temperature_scale))
+        .unwrap_or_else(|| status_name(snapshot.status).to_string())
+}
+
+pub(crate) fn status_name(status: ReadingStatus) -> &'static str {
+    match status {
+        ReadingStatus::Fresh => "fresh",
+        ReadingStatus::Stale => "stale",
+        ReadingStatus::NeverReceived => "waiting",
+    }
+}
+
+fn header(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+/// Prints `rows` as left-aligned columns.
+fn print_table(rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = vec![];
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(i) {
+                Some(max) => *max = (*max).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
@@ -18,137 +18,94 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use glib::ControlFlow::Continue;
-use glib::source::timeout_add_local;
-use gtk::prelude::*;
-use gtk::Orientation::{Horizontal, Vertical};
-use gtk::{Application, ApplicationWindow, Box, Frame, Label, Orientation, Widget};
+use std::process::ExitCode;
 
-use boiling_frog_dbus::dbus_engine::DbusEngine;
-use boiling_frog_dbus::GenericError;
+use clap::Parser;
 
-use crate::config::{MARGIN, UPDATE_RATE};
+#[cfg(feature = "dbus")]
+use boiling_frog_dbus::DbusSession;
+#[cfg(feature = "hwmon")]
+use boiling_frog_dbus::HwmonSession;
+use boiling_frog_dbus::{DbusEngine, GenericError};
 
+use crate::cli::{Cli, Command};
+use crate::config::Config;
+#[cfg(not(feature = "sqlite"))]
+use crate::error::NoDatabaseError;
+
+mod bar;
+mod cli;
 mod config;
+#[cfg(feature = "gui")]
+mod config_watcher;
+mod error;
+#[cfg(feature = "gui")]
+mod graph;
+#[cfg(feature = "gui")]
+mod gui;
+mod headless;
+mod recorder;
 mod ui_format;
-
-const APP_ID: &str = "com.robwilliamson.boiling_frog";
-const TITLE: &str = "Boiling Frog";
-
-fn main() -> glib::ExitCode {
-    // Create a new application
-    let app = Application::builder().application_id(APP_ID).build();
-
-    // Connect to "activate" signal of `app`
-    app.connect_activate(build_ui);
-
-    // Run the application
-    app.run()
-}
-
-fn build_ui(app: &Application) {
-    // Present window
-    match build_happy_path_ui(app) {
-        Ok(window) => window.present(),
-        Err(e) => {
-            println!("{}", e.to_string());
-            build_error_path_ui(app).present()
-        }
+mod watch;
+
+fn main() -> ExitCode {
+    // Parsed here too so that bad arguments & --help are reported before starting GTK.
+    let cli = Cli::parse();
+    match cli.command() {
+        Command::Gui => run_gui(),
+        command => match cli.load_config() {
+            Ok(config) => headless::run(command, &config, cli.record.as_deref()),
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        },
     }
 }
 
-fn build_error_path_ui(app: &Application) -> ApplicationWindow {
-    let grid = set_margins!(Box::builder(), MARGIN)
-        .orientation(Orientation::Vertical)
-        .build();
-    let label = set_margins!(Label::builder(), MARGIN)
-        .label("Could not receive device thermal data. Is Hardware Sensors Indicator installed?")
-        .selectable(true)
-        .build();
-    let link = set_margins!(Label::builder(), MARGIN)
-        .use_markup(true)
-        .label("<a href=\"https://github.com/alexmurray/indicator-sensors\">Hardware Sensors Indicator</a>")
-        .selectable(true)
-        .build();
-
-    grid.append(&label);
-    grid.append(&link);
-
-    make_window(app, &grid)
+#[cfg(feature = "gui")]
+fn run_gui() -> ExitCode {
+    gui::run()
 }
 
-fn build_happy_path_ui(app: &Application) -> Result<ApplicationWindow, GenericError> {
-    let engine = DbusEngine::new()?;
-
-    // Create Label
-    let temperature_title_label = set_margins!(Label::builder(), MARGIN)
-        .label("Maximum Temperature")
-        .build();
-
-    let temperature_value_label = set_margins!(Label::builder(), MARGIN)
-        .use_markup(true)
-        .label(make_value_units_string!(&engine.temp()))
-        .build();
-
-    // https://docs.gtk.org/gtk4/visual_index.html
-    let temperature_grid = set_margins!(Box::builder(), MARGIN)
-        .orientation(Orientation::Vertical)
-        .build();
-
-    temperature_grid.append(&temperature_title_label);
-    temperature_grid.append(&temperature_value_label);
-
-    let temperature_frame = set_margins!(Frame::builder(), MARGIN)
-        .child(&temperature_grid)
-        .build();
-
-    // Create Label
-    let fan_title = set_margins!(Label::builder(), MARGIN)
-        .label("Highest Fan Speed")
-        .build();
-
-    // https://docs.gtk.org/Pango/pango_markup.html
-    let fan_speed = set_margins!(Label::builder(), MARGIN)
-        .use_markup(true)
-        .label(make_value_units_string!(&engine.fan()))
-        .build();
-
-    // https://docs.gtk.org/gtk4/visual_index.html
-    let fan_grid = set_margins!(Box::builder(), MARGIN)
-        .orientation(Vertical)
-        .build();
-
-    fan_grid.append(&fan_title);
-    fan_grid.append(&fan_speed);
-
-    let fan_frame = set_margins!(Frame::builder(), MARGIN)
-        .child(&fan_grid)
-        .build();
-
-    let metrics_grid = set_margins!(Box::builder(), MARGIN)
-        .orientation(Horizontal)
-        .build();
-
-    metrics_grid.append(&temperature_frame);
-    metrics_grid.append(&fan_frame);
-
-    let gtk_box = Box::builder().orientation(Vertical).build();
-    gtk_box.append(&metrics_grid);
-
-    // Poll the engine because GTK is not thread-safe.
-    timeout_add_local(UPDATE_RATE, move || {
-        fan_speed.set_label(&make_value_units_string!(&engine.fan()));
-        temperature_value_label.set_label(&make_value_units_string!(&engine.temp()));
-        Continue
-    });
-
-    Ok(make_window(app, &gtk_box))
+#[cfg(not(feature = "gui"))]
+fn run_gui() -> ExitCode {
+    eprintln!("This build has no window; try `list`, `dump` or `watch`.");
+    ExitCode::FAILURE
 }
 
-fn make_window(app: &Application, child: &impl IsA<Widget>) -> ApplicationWindow {
-    ApplicationWindow::builder()
-        .application(app)
-        .title(TITLE)
-        .child(child)
-        .build()
+pub(crate) fn build_engine(config: &Config) -> Result<DbusEngine, GenericError> {
+    let mut builder = DbusEngine::builder();
+    #[cfg(feature = "dbus")]
+    builder.with_backend(Box::new(DbusSession::with_service(
+        &config.dbus.service,
+        &config.dbus.path,
+    )));
+    #[cfg(feature = "hwmon")]
+    builder.with_backend(Box::new(HwmonSession::new()));
+    builder
+        .with_filter(&config.filters)
+        .and()
+        .with_stale_after(config.stale_after())
+        .and()
+        .with_thresholds(&config.all_thresholds())
+        .and()
+        .with_trends(&config.trend_settings())
+        .and()
+        .with_alert_settings(&config.notifications.alert_settings());
+    for panel in &config.panels {
+        builder.with_aggregation(panel.kind, panel.aggregation.clone());
+    }
+    if let Some(path) = config.baseline.path().filter(|_| config.baseline.enabled) {
+        builder.with_baseline_file(&path);
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = config.database.path().filter(|_| config.database.enabled) {
+        builder.with_database(&config.database.settings(&path));
+    }
+    #[cfg(not(feature = "sqlite"))]
+    if config.database.enabled {
+        return Err(Box::new(NoDatabaseError {}));
+    }
+    builder.build()
 }
@@ -0,0 +1,328 @@
+use std::fs::{create_dir_all, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+#[cfg(feature = "gui")]
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use boiling_frog_dbus::{
+    DbusEngine, GenericError, SensorSnapshot, SubscriptionId, TemperatureScale, Units,
+};
+
+use crate::watch::{csv_field, serialize_units};
+
+/// How a recording is written, going by its file's extension.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RecordingFormat {
+    /// Comma-separated values, with a header line, for files ending in `.csv`.
+    Csv,
+    /// A JSON object per line, for any other file.
+    Jsonl,
+}
+
+impl RecordingFormat {
+    pub fn of(path: &Path) -> RecordingFormat {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => RecordingFormat::Csv,
+            _ => RecordingFormat::Jsonl,
+        }
+    }
+}
+
+/// One line of a recording.
+#[derive(Serialize)]
+struct Line<'a> {
+    /// Seconds since the Unix epoch.
+    time: f64,
+    id: &'a str,
+    label: &'a str,
+    kind: &'static str,
+    #[serde(serialize_with = "serialize_units")]
+    units: Units,
+    value: f64,
+}
+
+const CSV_HEADER: &str = "time,id,label,kind,units,value";
+/// How often the recording is synced to disk, so that a crash or power cut loses at most the
+/// readings since.
+const SYNC_EVERY: Duration = Duration::from_secs(1);
+
+/// What a [`Recorder`]'s thread is sent.
+enum Message {
+    Line(String),
+    /// Syncs the lines written so far & stops the thread.
+    Stop,
+}
+
+/// Appends every sensor reading to a file, a line at a time. The lines are written on their own
+/// thread, so that the sensors aren't held up by the disk.
+pub struct Recorder {
+    format: RecordingFormat,
+    #[cfg(feature = "gui")]
+    path: PathBuf,
+    sender: Sender<Message>,
+    subscription: Option<SubscriptionId>,
+    temperature_scale: TemperatureScale,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Recorder {
+    /// Opens the file at `path` to append to, writing the CSV header if it's a new CSV file.
+    pub fn new(path: &Path, temperature_scale: TemperatureScale) -> Result<Recorder, GenericError> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let format = RecordingFormat::of(path);
+        if format == RecordingFormat::Csv && file.metadata()?.len() == 0 {
+            writeln!(file, "{}", CSV_HEADER)?;
+        }
+        let (sender, receiver) = channel();
+        let thread = thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || {
+                let mut last_sync = Instant::now();
+                let mut is_synced = true;
+                loop {
+                    let timeout = SYNC_EVERY.saturating_sub(last_sync.elapsed());
+                    let is_stopping = match receiver.recv_timeout(timeout) {
+                        Ok(Message::Line(line)) => {
+                            // Files aren't buffered, so each line is handed to the OS whole.
+                            if let Err(e) = file.write_all(line.as_bytes()) {
+                                eprintln!("Could not record a reading: {e}");
+                            }
+                            is_synced = false;
+                            false
+                        }
+                        Err(RecvTimeoutError::Timeout) => false,
+                        Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => true,
+                    };
+                    if is_stopping || last_sync.elapsed() >= SYNC_EVERY {
+                        last_sync = Instant::now();
+                        if !is_synced {
+                            if let Err(e) = file.sync_data() {
+                                eprintln!("Could not sync the recording: {e}");
+                            }
+                            is_synced = true;
+                        }
+                    }
+                    if is_stopping {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(Recorder {
+            format,
+            #[cfg(feature = "gui")]
+            path: path.to_path_buf(),
+            sender,
+            subscription: None,
+            temperature_scale,
+            thread: Some(thread),
+        })
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records every reading `engine` receives from now on. An engine that replaces the one
+    /// recorded from has to be attached too.
+    pub fn attach(&mut self, engine: &DbusEngine) -> Result<(), GenericError> {
+        let sender = self.sender.clone();
+        let format = self.format;
+        let temperature_scale = self.temperature_scale;
+        self.subscription = Some(engine.subscribe_all(&Arc::new(Mutex::new(Box::new(
+            move |snapshot: SensorSnapshot| {
+                let Some(value) = snapshot.value else {
+                    return Ok(());
+                };
+                let (value, units) = temperature_scale.apply(value, &snapshot.units);
+                let line = Line {
+                    time: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64(),
+                    id: &snapshot.id,
+                    label: &snapshot.label,
+                    kind: snapshot.kind.name(),
+                    units,
+                    value,
+                };
+                let text = match format {
+                    RecordingFormat::Csv => csv(&line),
+                    RecordingFormat::Jsonl => serde_json::to_string(&line)?,
+                };
+                // The thread only stops as the recorder is dropped.
+                let _ = sender.send(Message::Line(format!("{text}\n")));
+                Ok(())
+            },
+        ))))?);
+        Ok(())
+    }
+
+    /// Stops recording the readings `engine` receives.
+    #[cfg(feature = "gui")]
+    pub fn detach(&mut self, engine: &DbusEngine) -> Result<(), GenericError> {
+        if let Some(subscription) = self.subscription.take() {
+            engine.unsubscribe(subscription)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    /// Syncs the lines written so far & stops the thread.
+    fn drop(&mut self) {
+        let _ = self.sender.send(Message::Stop);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                eprintln!("The recorder's thread panicked");
+            }
+        }
+    }
+}
+
+fn csv(line: &Line) -> String {
+    [
+        format!("{:.3}", line.time),
+        csv_field(line.id),
+        csv_field(line.label),
+        line.kind.to_string(),
+        csv_field(line.units.symbol()),
+        line.value.to_string(),
+    ]
+    .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn line<'a>(id: &'a str, label: &'a str, units: Units) -> Line<'a> {
+        Line {
+            time: 1_700_000_000.25,
+            id,
+            label,
+            kind: "temp",
+            units,
+            value: 45.5,
+        }
+    }
+
+    #[test]
+    fn picks_the_format_by_extension() {
+        assert_eq!(
+            RecordingFormat::of(Path::new("readings.CSV")),
+            RecordingFormat::Csv
+        );
+        assert_eq!(
+            RecordingFormat::of(Path::new("readings.jsonl")),
+            RecordingFormat::Jsonl
+        );
+        assert_eq!(
+            RecordingFormat::of(Path::new("readings")),
+            RecordingFormat::Jsonl
+        );
+    }
+
+    #[test]
+    fn writes_csv_in_the_headers_order() {
+        assert_eq!(
+            csv(&line("/cpu", "CPU", Units::Celsius)),
+            "1700000000.250,/cpu,CPU,temp,℃,45.5"
+        );
+    }
+
+    #[test]
+    fn quotes_csv_fields_with_commas_quotes_or_newlines() {
+        assert_eq!(
+            csv(&line(
+                "/a,b",
+                "the \"hot\" one\n",
+                Units::Other("a,b".to_string())
+            )),
+            "1700000000.250,\"/a,b\",\"the \"\"hot\"\" one\n\",temp,\"a,b\",45.5"
+        );
+    }
+
+    #[test]
+    fn names_the_jsonl_fields_like_the_csv_header() {
+        let json: serde_json::Value = serde_json::from_str(
+            &serde_json::to_string(&line("/cpu", "CPU", Units::Celsius)).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "time": 1_700_000_000.25,
+                "id": "/cpu",
+                "label": "CPU",
+                "kind": "temp",
+                "units": "℃",
+                "value": 45.5,
+            })
+        );
+        let mut fields: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut header: Vec<&str> = CSV_HEADER.split(',').collect();
+        fields.sort();
+        header.sort();
+        assert_eq!(fields, header);
+    }
+
+    #[test]
+    fn writes_the_csv_header_only_to_a_new_file() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("recordings").join("readings.csv");
+
+        drop(Recorder::new(&path, TemperatureScale::Celsius).unwrap());
+        drop(Recorder::new(&path, TemperatureScale::Celsius).unwrap());
+
+        assert_eq!(read_to_string(&path).unwrap(), format!("{CSV_HEADER}\n"));
+    }
+
+    #[test]
+    fn writes_no_header_to_a_jsonl_file() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("readings.jsonl");
+
+        drop(Recorder::new(&path, TemperatureScale::Celsius).unwrap());
+
+        assert_eq!(read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn writes_the_lines_it_is_sent_before_stopping() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("readings.csv");
+        let recorder = Recorder::new(&path, TemperatureScale::Celsius).unwrap();
+
+        recorder
+            .sender
+            .send(Message::Line("1,/cpu,CPU,temp,℃,45\n".to_string()))
+            .unwrap();
+        drop(recorder);
+
+        assert_eq!(
+            read_to_string(&path).unwrap(),
+            format!("{CSV_HEADER}\n1,/cpu,CPU,temp,℃,45\n")
+        );
+    }
+}
@@ -1,3 +1,14 @@
+#[cfg(any(feature = "dbus", feature = "gui"))]
+use std::time::Duration;
+
+#[cfg(any(feature = "dbus", feature = "gui"))]
+use boiling_frog_dbus::Trend;
+#[cfg(any(feature = "dbus", feature = "gui"))]
+use boiling_frog_dbus::{Alert, AlertCause};
+use boiling_frog_dbus::{
+    Condition, Drift, Level, Load, ReadingStatus, SensorSnapshot, TemperatureScale, Units, FAN_BAND,
+};
+
 #[macro_export]
 macro_rules! set_margins {
     ($element_builder:expr, $margin:expr) => {
@@ -11,17 +22,171 @@ macro_rules! set_margins {
 
 #[macro_export]
 macro_rules! make_value_units_string {
-    ($source:expr) => {{
+    ($source:expr, $font_size:expr, $temperature_scale:expr) => {{
+        // Pango font sizes are in 1024ths of a point.
+        let font_size = ($font_size * 1024.0) as i64;
         $source
             .as_ref()
             .map(|val| {
+                let value =
+                    $crate::ui_format::format_value(val.value, &val.units, $temperature_scale);
+                // Means & medians don't come from any one sensor, so they have no label.
+                let text = if val.label.is_empty() {
+                    value
+                } else {
+                    format!("{} {}", val.label, value)
+                };
                 // https://docs.gtk.org/Pango/pango_markup.html
-                format!(
-                    "<span font_size='40000'>{} {:.0}{}</span>",
-                    val.label, val.value, val.units
-                )
-                .to_string()
+                match val.status {
+                    boiling_frog_dbus::ReadingStatus::NeverReceived => {
+                        format!("<span font_size='{}'>waiting…</span>", font_size)
+                    }
+                    boiling_frog_dbus::ReadingStatus::Stale => {
+                        format!(
+                            "<span font_size='{}' alpha='50%'>{}</span>",
+                            font_size, text
+                        )
+                    }
+                    boiling_frog_dbus::ReadingStatus::Fresh => {
+                        format!("<span font_size='{}'>{}</span>", font_size, text)
+                    }
+                }
             })
             .unwrap_or("unable to get value".to_string())
     }};
 }
+
+/// How many decimal places are worth showing for a value in `units`.
+pub fn decimals(units: &Units) -> usize {
+    match units {
+        Units::Volts | Units::Amps => 2,
+        Units::Watts | Units::Joules => 1,
+        _ => 0,
+    }
+}
+
+/// Formats `value` in `units` with a sensible number of decimal places, showing temperatures in
+/// `temperature_scale`.
+pub fn format_value(value: f64, units: &Units, temperature_scale: TemperatureScale) -> String {
+    let (value, units) = temperature_scale.apply(value, units);
+    format!("{:.*}{}", decimals(&units), value, units)
+}
+
+/// A change of `change` in `units`, with its sign, e.g. "+8℃". Unlike temperatures, changes in
+/// temperature aren't offset in Fahrenheit.
+pub fn format_change(change: f64, units: &Units, temperature_scale: TemperatureScale) -> String {
+    let (zero, _) = temperature_scale.apply(0.0, units);
+    let (change, units) = temperature_scale.apply(change, units);
+    format!("{:+.*}{}", decimals(&units), change - zero, units)
+}
+
+/// The conditions readings were compared under, e.g. "with fans at 2000–2500RPM" or "when idle".
+pub fn condition_text(condition: &Condition) -> String {
+    match condition {
+        Condition::FanSpeed(speed) => format!(
+            "with fans at {speed}–{}{}",
+            speed + FAN_BAND,
+            Units::Rpm.symbol()
+        ),
+        Condition::Load(Load::Idle) => "when idle".to_string(),
+        Condition::Load(Load::Full) => "under full load".to_string(),
+    }
+}
+
+/// How a reading has drifted, e.g. "+8℃ with fans at 2000–2500RPM".
+pub fn drift_text(drift: &Drift, temperature_scale: TemperatureScale) -> String {
+    format!(
+        "{} {}",
+        format_change(drift.change(), &drift.kind.units(), temperature_scale),
+        condition_text(&drift.condition)
+    )
+}
+
+/// A sensor's label & latest reading, e.g. "CPU 54℃".
+pub fn sensor_line(snapshot: &SensorSnapshot, temperature_scale: TemperatureScale) -> String {
+    match (snapshot.value, snapshot.status) {
+        (Some(value), ReadingStatus::Stale) => format!(
+            "{} {} (stale)",
+            snapshot.label,
+            format_value(value, &snapshot.units, temperature_scale)
+        ),
+        (Some(value), _) => format!(
+            "{} {}",
+            snapshot.label,
+            format_value(value, &snapshot.units, temperature_scale)
+        ),
+        (None, _) => format!("{} waiting…", snapshot.label),
+    }
+}
+
+/// The summary & body of a notification about `alert`, e.g. "GPU is critical" & "96℃, over the
+/// 95℃ threshold".
+#[cfg(any(feature = "dbus", feature = "gui"))]
+pub fn alert_text(alert: &Alert, temperature_scale: TemperatureScale) -> (String, String) {
+    let value = format_value(alert.value, &alert.units, temperature_scale);
+    let (summary, mut body) = match alert.cause {
+        AlertCause::Threshold { level, threshold } => (
+            format!("{} is {}", alert.label, level.name()),
+            format!(
+                "{value}, over the {} threshold",
+                format_value(threshold, &alert.units, temperature_scale)
+            ),
+        ),
+        AlertCause::Rising => (format!("{} is creeping up", alert.label), value),
+    };
+    if let Some(trend) = &alert.trend {
+        body.push_str(&format!(
+            ", {}",
+            trend_text(trend, &alert.units, temperature_scale)
+        ));
+    }
+
+    (summary, body)
+}
+
+#[cfg(any(feature = "dbus", feature = "gui"))]
+/// How fast a reading in `units` is changing & when it'll be critical at that rate, e.g.
+/// "rising 1.2℃/min, critical in 25 min".
+pub fn trend_text(trend: &Trend, units: &Units, temperature_scale: TemperatureScale) -> String {
+    // Rates of change aren't offset like temperatures are.
+    let (zero, _) = temperature_scale.apply(0.0, units);
+    let (rate, units) = temperature_scale.apply(trend.rate, units);
+    let direction = if trend.rate < 0.0 {
+        "falling"
+    } else {
+        "rising"
+    };
+    let mut text = format!(
+        "{direction} {:.*}{units}/min",
+        decimals(&units) + 1,
+        (rate - zero).abs()
+    );
+    if let Some(time_to_critical) = trend.time_to_critical {
+        text.push_str(&format!(
+            ", critical in {}",
+            format_duration(time_to_critical)
+        ));
+    }
+    text
+}
+
+#[cfg(any(feature = "dbus", feature = "gui"))]
+/// A rough duration, e.g. "25 min" or "2 h 5 min".
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    match minutes {
+        0 => "under a minute".to_string(),
+        1..=59 => format!("{minutes} min"),
+        _ => format!("{} h {} min", minutes / 60, minutes % 60),
+    }
+}
+
+/// The colour that marks a reading at `level`, as RGB, or `None` for normal readings.
+pub fn level_colour(level: Level) -> Option<(u8, u8, u8)> {
+    match level {
+        Level::Normal => None,
+        Level::Warm => Some((0xff, 0xb0, 0x00)),
+        Level::Hot => Some((0xff, 0x60, 0x00)),
+        Level::Critical => Some((0xff, 0x00, 0x00)),
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::io::{stdout, Write};
+use std::thread::sleep;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use serde::{Serialize, Serializer};
+
+use boiling_frog_dbus::{DbusEngine, GenericError, ReadingStatus, Units};
+
+use crate::cli::WatchArgs;
+use crate::config::Config;
+use crate::headless::{status_name, wait_for_first_readings};
+use crate::ui_format::format_value;
+
+/// How `watch` prints readings.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Columns for reading in a terminal.
+    #[default]
+    Text,
+    /// A JSON object per line.
+    Jsonl,
+    /// Comma-separated values, with a header line.
+    Csv,
+}
+
+/// One line of `watch` output.
+#[derive(Serialize)]
+struct Record {
+    /// Seconds since the Unix epoch.
+    time: f64,
+    /// `"aggregate"` for a panel's value, or `"sensor"`.
+    #[serde(rename = "type")]
+    record_type: &'static str,
+    kind: &'static str,
+    /// The panel title or sensor label.
+    name: String,
+    /// The sensor id, or for an aggregate, the label of the sensor it came from.
+    id: String,
+    /// `None` until the first reading is received.
+    value: Option<f64>,
+    #[serde(serialize_with = "serialize_units")]
+    units: Units,
+    status: &'static str,
+}
+
+impl Record {
+    fn key(&self) -> String {
+        format!("{} {} {}", self.record_type, self.kind, self.id)
+    }
+}
+
+const CSV_HEADER: &str = "time,type,kind,name,id,value,units,status";
+
+/// Prints readings every `update_rate_ms`, until interrupted or stdout is closed.
+pub fn run(engine: &DbusEngine, config: &Config, args: WatchArgs) -> Result<(), GenericError> {
+    wait_for_first_readings(engine)?;
+
+    let mut out = stdout().lock();
+    match args.format {
+        Format::Text => writeln!(
+            out,
+            "{:<12}  {:<9}  {:<11}  {:<24}  {:>10}  {:<7}  ID",
+            "TIME (UTC)", "TYPE", "KIND", "NAME", "VALUE", "STATUS"
+        )?,
+        Format::Jsonl => (),
+        Format::Csv => writeln!(out, "{}", CSV_HEADER)?,
+    }
+
+    // The value & status last printed for each record, so that unchanged ones can be skipped.
+    let mut printed: HashMap<String, (Option<f64>, &'static str)> = HashMap::new();
+    loop {
+        for record in records(engine, config)? {
+            let state = (record.value, record.status);
+            if args.on_change && printed.get(&record.key()) == Some(&state) {
+                continue;
+            }
+            printed.insert(record.key(), state);
+
+            match args.format {
+                Format::Text => writeln!(out, "{}", text(&record, config))?,
+                Format::Jsonl => writeln!(out, "{}", serde_json::to_string(&record)?)?,
+                Format::Csv => writeln!(out, "{}", csv(&record))?,
+            }
+        }
+        out.flush()?;
+        sleep(config.update_rate());
+    }
+}
+
+/// The current value of each panel that's shown, followed by each sensor's.
+fn records(engine: &DbusEngine, config: &Config) -> Result<Vec<Record>, GenericError> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+    let scale = config.temperature_scale;
+    let snapshots = engine.sensors()?;
+    let mut records = vec![];
+
+    for panel in config
+        .panels
+        .iter()
+        .filter(|panel| panel.is_wanted(&snapshots))
+    {
+        let reading = engine.reading(panel.kind)?;
+        let (value, units) = scale.apply(reading.value, &reading.units);
+        records.push(Record {
+            time,
+            record_type: "aggregate",
+            kind: panel.kind.name(),
+            name: panel.title(),
+            id: reading.label,
+            value: (reading.status != ReadingStatus::NeverReceived).then_some(value),
+            units,
+            status: status_name(reading.status),
+        });
+    }
+
+    for snapshot in snapshots {
+        let (value, units) = scale.apply(snapshot.value.unwrap_or_default(), &snapshot.units);
+        records.push(Record {
+            time,
+            record_type: "sensor",
+            kind: snapshot.kind.name(),
+            name: snapshot.label,
+            id: snapshot.id,
+            value: snapshot.value.map(|_| value),
+            units,
+            status: status_name(snapshot.status),
+        });
+    }
+
+    Ok(records)
+}
+
+pub(crate) fn serialize_units<S: Serializer>(
+    units: &Units,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(units.symbol())
+}
+
+fn text(record: &Record, config: &Config) -> String {
+    let seconds_today = record.time % 86_400.0;
+    let value = record
+        .value
+        .map(|value| format_value(value, &record.units, config.temperature_scale))
+        .unwrap_or_default();
+    format!(
+        "{:02}:{:02}:{:06.3}  {:<9}  {:<11}  {:<24}  {:>10}  {:<7}  {}",
+        (seconds_today / 3600.0) as u64,
+        (seconds_today % 3600.0 / 60.0) as u64,
+        seconds_today % 60.0,
+        record.record_type,
+        record.kind,
+        record.name,
+        value,
+        record.status,
+        record.id
+    )
+}
+
+fn csv(record: &Record) -> String {
+    [
+        format!("{:.3}", record.time),
+        record.record_type.to_string(),
+        record.kind.to_string(),
+        csv_field(&record.name),
+        csv_field(&record.id),
+        record
+            .value
+            .map(|value| value.to_string())
+            .unwrap_or_default(),
+        csv_field(record.units.symbol()),
+        record.status.to_string(),
+    ]
+    .join(",")
+}
+
+/// Quotes `field` if it contains anything that would break the CSV.
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, value: Option<f64>) -> Record {
+        Record {
+            time: 1_700_000_000.25,
+            record_type: "sensor",
+            kind: "temp",
+            name: name.to_string(),
+            id: "/cpu".to_string(),
+            value,
+            units: Units::Celsius,
+            status: "fresh",
+        }
+    }
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        assert_eq!(csv_field("CPU"), "CPU");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("the \"hot\" one"), "\"the \"\"hot\"\" one\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+    }
+
+    #[test]
+    fn writes_csv_in_the_headers_order() {
+        assert_eq!(
+            csv(&record("Core 0, package", Some(45.5))),
+            "1700000000.250,sensor,temp,\"Core 0, package\",/cpu,45.5,℃,fresh"
+        );
+        assert_eq!(
+            csv(&record("CPU", None)),
+            "1700000000.250,sensor,temp,CPU,/cpu,,℃,fresh"
+        );
+    }
+
+    #[test]
+    fn names_the_jsonl_fields_like_the_csv_header() {
+        let json: serde_json::Value = serde_json::to_value(record("CPU", Some(45.5))).unwrap();
+        let mut keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut header: Vec<&str> = CSV_HEADER.split(',').collect();
+        keys.sort_unstable();
+        header.sort_unstable();
+        assert_eq!(keys, header);
+        assert_eq!(json["type"], "sensor");
+        assert_eq!(json["units"], "℃");
+    }
+
+    #[test]
+    fn writes_text_with_the_time_of_day_and_a_blank_unknown_value() {
+        let line = text(&record("CPU", None), &Config::default());
+        assert!(line.starts_with("22:13:20.250  sensor     temp         CPU"));
+        assert!(line.ends_with("fresh    /cpu"));
+    }
+}
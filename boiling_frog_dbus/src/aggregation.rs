@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::simple_types::SensorKind;
+
+/// How the readings from a group of sensors are reduced to the single value a panel shows.
+///
+/// In a config file, this is `"max"`, `"min"`, `"mean"`, `"median"`, `{ pinned = "CPU" }` or
+/// `{ weighted_mean = { CPU = 2.0, GPU = 1.0 } }`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    #[default]
+    Max,
+    Min,
+    Mean,
+    Median,
+    /// The reading from one sensor, chosen by its id or label.
+    Pinned(String),
+    /// The mean of the readings from the sensors with a weight, keyed by sensor id or label.
+    /// Sensors without a weight are ignored.
+    WeightedMean(HashMap<String, f64>),
+}
+
+impl Aggregation {
+    /// A title for a panel showing this aggregation of `kind` sensors.
+    pub fn title(&self, kind: SensorKind) -> String {
+        let quantity = kind.quantity();
+        match (self, kind) {
+            (Aggregation::Max, SensorKind::Fan) => "Highest Fan Speed".to_string(),
+            (Aggregation::Max, _) => format!("Maximum {quantity}"),
+            (Aggregation::Min, SensorKind::Fan) => "Lowest Fan Speed".to_string(),
+            (Aggregation::Min, _) => format!("Minimum {quantity}"),
+            (Aggregation::Mean, _) => format!("Average {quantity}"),
+            (Aggregation::Median, _) => format!("Median {quantity}"),
+            (Aggregation::Pinned(sensor), _) => format!("{sensor} {quantity}"),
+            (Aggregation::WeightedMean(_), _) => format!("Weighted Average {quantity}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_fans_by_speed() {
+        assert_eq!(Aggregation::Max.title(SensorKind::Fan), "Highest Fan Speed");
+        assert_eq!(Aggregation::Min.title(SensorKind::Fan), "Lowest Fan Speed");
+    }
+
+    #[test]
+    fn titles_other_kinds_by_quantity() {
+        let quantity = SensorKind::Temp.quantity();
+        let titles = [
+            (Aggregation::Max, format!("Maximum {quantity}")),
+            (Aggregation::Min, format!("Minimum {quantity}")),
+            (Aggregation::Mean, format!("Average {quantity}")),
+            (Aggregation::Median, format!("Median {quantity}")),
+            (
+                Aggregation::Pinned("CPU".to_string()),
+                format!("CPU {quantity}"),
+            ),
+            (
+                Aggregation::WeightedMean(HashMap::new()),
+                format!("Weighted Average {quantity}"),
+            ),
+        ];
+        for (aggregation, title) in titles {
+            assert_eq!(aggregation.title(SensorKind::Temp), title);
+        }
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::history::Sample;
+use crate::simple_types::{SensorKind, SubscriptionId};
+use crate::thresholds::{Level, Thresholds};
+use crate::trend::{Trend, TrendSettings};
+use crate::units::Units;
+use crate::GenericError;
+
+/// How long the "Snooze" action on a notification silences the alerts about its reading for.
+pub const SNOOZE: Duration = Duration::from_secs(10 * 60);
+
+/// The reading an [`Alert`] is about.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum AlertSource {
+    /// The aggregate reading of a kind.
+    Aggregate(SensorKind),
+    /// The sensor with this id.
+    Sensor(String),
+}
+
+/// Why an [`Alert`] was raised.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlertCause {
+    /// The reading rose past the `threshold` for `level`, in canonical units.
+    Threshold { level: Level, threshold: f64 },
+    /// The reading has been rising at least as fast as its kind's [`TrendSettings::rate`] for
+    /// the whole [`TrendSettings::period`], whether or not it has crossed a threshold.
+    Rising,
+}
+
+/// A reading that has risen into a higher [`Level`], or has been rising steadily.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alert {
+    pub source: AlertSource,
+    /// The sensor's label, or for an aggregate, the label of the reading it came from or its
+    /// kind's [`SensorKind::quantity`] if it comes from several, e.g. a mean.
+    pub label: String,
+    pub kind: SensorKind,
+    pub cause: AlertCause,
+    /// The reading, in canonical units.
+    pub value: f64,
+    pub units: Units,
+    /// How the reading has been changing, if its kind has [`TrendSettings`].
+    pub trend: Option<Trend>,
+}
+
+impl Alert {
+    /// The level the reading rose into, or [`Level::Normal`] for a steady rise.
+    pub fn level(&self) -> Level {
+        match self.cause {
+            AlertCause::Threshold { level, .. } => level,
+            AlertCause::Rising => Level::Normal,
+        }
+    }
+}
+
+pub type AlertCallback =
+    Arc<Mutex<Box<dyn Fn(Alert) -> Result<(), GenericError> + Send + Sync + 'static>>>;
+
+/// When readings raise alerts, so that a reading hovering around a threshold doesn't raise a
+/// flood of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlertSettings {
+    /// How far below a level's threshold a reading has to fall to leave the level, as a fraction
+    /// of the threshold. Defaults to 0.05, e.g. below 90.25℃ to stop being critical at 95℃.
+    /// Likewise, a rising reading keeps rising until its rate falls this fraction below the
+    /// [`TrendSettings::rate`].
+    pub hysteresis: f64,
+    /// The shortest time between alerts about the same reading, unless it reaches a higher level
+    /// than last time. Defaults to 5 minutes.
+    pub cooldown: Duration,
+    /// Raises alerts about each sensor as well as the aggregates. Defaults to false.
+    pub per_sensor: bool,
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        AlertSettings {
+            hysteresis: 0.05,
+            cooldown: Duration::from_secs(5 * 60),
+            per_sensor: false,
+        }
+    }
+}
+
+#[derive(Default)]
+struct AlertState {
+    level: Level,
+    /// When the last threshold alert was raised, & at what level.
+    last_alert: Option<(Instant, Level)>,
+    is_rising: bool,
+    last_rising_alert: Option<Instant>,
+}
+
+/// Tracks the level & trend of each reading, & raises alerts as they rise through the thresholds
+/// or rise steadily.
+pub(crate) struct Alerts {
+    callbacks: Vec<(SubscriptionId, AlertCallback)>,
+    next_subscription: u64,
+    settings: AlertSettings,
+    snoozed_until: Option<Instant>,
+    /// When the readings snoozed on their own can raise alerts again.
+    source_snoozed_until: HashMap<AlertSource, Instant>,
+    states: HashMap<AlertSource, AlertState>,
+    thresholds: HashMap<SensorKind, Thresholds>,
+    trends: HashMap<SensorKind, TrendSettings>,
+}
+
+impl Alerts {
+    pub(crate) fn new(
+        settings: &AlertSettings,
+        thresholds: &HashMap<SensorKind, Thresholds>,
+        trends: &HashMap<SensorKind, TrendSettings>,
+    ) -> Alerts {
+        Alerts {
+            callbacks: vec![],
+            next_subscription: 0,
+            settings: *settings,
+            snoozed_until: None,
+            source_snoozed_until: HashMap::new(),
+            states: HashMap::new(),
+            thresholds: thresholds.clone(),
+            trends: trends.clone(),
+        }
+    }
+
+    /// How far back [`Alerts::check`] & [`Alerts::trend`] need the readings of `kind` to go, if
+    /// its trend is followed.
+    pub(crate) fn trend_period(&self, kind: SensorKind) -> Option<Duration> {
+        self.trends.get(&kind).map(|trend| trend.period)
+    }
+
+    /// Whether readings of `kind` from `source` can raise any alerts, so that there's no need to
+    /// fetch their samples otherwise.
+    pub(crate) fn is_checked(&self, source: &AlertSource, kind: SensorKind) -> bool {
+        (self.settings.per_sensor || matches!(source, AlertSource::Aggregate(_)))
+            && (self.thresholds.contains_key(&kind) || self.trends.contains_key(&kind))
+    }
+
+    /// How `samples` of a `kind` reading, from [`Alerts::trend_period`] before `now`, have been
+    /// changing.
+    pub(crate) fn trend(
+        &self,
+        kind: SensorKind,
+        samples: &[Sample],
+        now: Instant,
+    ) -> Option<Trend> {
+        let period = self.trend_period(kind)?;
+        let critical = self
+            .thresholds
+            .get(&kind)
+            .map(|thresholds| thresholds.critical);
+        Trend::fit(samples, now, period, critical)
+    }
+
+    /// Records a new reading from `source`, with its `samples` from [`Alerts::trend_period`] ago,
+    /// returning the alerts it raises & the callbacks to call with them.
+    pub(crate) fn check(
+        &mut self,
+        source: AlertSource,
+        label: &str,
+        kind: SensorKind,
+        value: f64,
+        units: &Units,
+        samples: &[Sample],
+    ) -> (Vec<Alert>, Vec<AlertCallback>) {
+        if !self.is_checked(&source, kind) {
+            return (vec![], vec![]);
+        }
+        let now = Instant::now();
+        let trend = self.trend(kind, samples, now);
+        let is_snoozed = self
+            .snoozed_until
+            .iter()
+            .chain(self.source_snoozed_until.get(&source))
+            .any(|until| now < *until);
+        let settings = self.settings;
+        let state = self.states.entry(source.clone()).or_default();
+        let mut causes = vec![];
+
+        if let Some(thresholds) = self.thresholds.get(&kind) {
+            let previous = state.level;
+            state.level = level_with_hysteresis(thresholds, previous, value, settings.hysteresis);
+            let is_cooling_down = state.last_alert.is_some_and(|(time, level)| {
+                level >= state.level && now.saturating_duration_since(time) < settings.cooldown
+            });
+            let threshold = thresholds.threshold(state.level);
+            if let Some(threshold) =
+                threshold.filter(|_| state.level > previous && !is_snoozed && !is_cooling_down)
+            {
+                state.last_alert = Some((now, state.level));
+                causes.push(AlertCause::Threshold {
+                    level: state.level,
+                    threshold,
+                });
+            }
+        }
+
+        if let Some(settings_rate) = self.trends.get(&kind).map(|trend| trend.rate) {
+            let was_rising = state.is_rising;
+            let rate = trend.map(|trend| trend.rate).unwrap_or_default();
+            state.is_rising = if was_rising {
+                rate >= settings_rate - settings_rate.abs() * settings.hysteresis
+            } else {
+                rate >= settings_rate
+            };
+            let is_cooling_down = state
+                .last_rising_alert
+                .is_some_and(|time| now.saturating_duration_since(time) < settings.cooldown);
+            if state.is_rising && !was_rising && !is_snoozed && !is_cooling_down {
+                state.last_rising_alert = Some(now);
+                causes.push(AlertCause::Rising);
+            }
+        }
+
+        if causes.is_empty() {
+            return (vec![], vec![]);
+        }
+        let alerts = causes
+            .into_iter()
+            .map(|cause| Alert {
+                source: source.clone(),
+                label: label.to_string(),
+                kind,
+                cause,
+                value,
+                units: units.clone(),
+                trend,
+            })
+            .collect();
+        let callbacks = self
+            .callbacks
+            .iter()
+            .map(|(_, callback)| callback.clone())
+            .collect();
+
+        (alerts, callbacks)
+    }
+
+    /// Forgets the level of a reading that has gone away.
+    pub(crate) fn forget(&mut self, source: &AlertSource) {
+        self.states.remove(source);
+    }
+
+    pub(crate) fn set_settings(&mut self, settings: &AlertSettings) {
+        self.settings = *settings;
+    }
+
+    pub(crate) fn set_thresholds(&mut self, thresholds: &HashMap<SensorKind, Thresholds>) {
+        self.thresholds = thresholds.clone();
+    }
+
+    pub(crate) fn set_trends(&mut self, trends: &HashMap<SensorKind, TrendSettings>) {
+        self.trends = trends.clone();
+    }
+
+    /// Stops raising alerts for `duration`. Readings' levels are still tracked meanwhile.
+    pub(crate) fn snooze(&mut self, duration: Duration) {
+        self.snoozed_until = Instant::now().checked_add(duration);
+    }
+
+    /// Stops raising alerts about the reading from `source` for `duration`, as [`Alerts::snooze`]
+    /// does for every reading.
+    pub(crate) fn snooze_source(&mut self, source: &AlertSource, duration: Duration) {
+        let now = Instant::now();
+        self.source_snoozed_until.retain(|_, until| now < *until);
+        if let Some(until) = now.checked_add(duration) {
+            self.source_snoozed_until.insert(source.clone(), until);
+        }
+    }
+
+    pub(crate) fn subscribe(&mut self, callback: &AlertCallback) -> SubscriptionId {
+        let subscription_id = SubscriptionId(self.next_subscription);
+        self.next_subscription += 1;
+        self.callbacks.push((subscription_id, callback.clone()));
+        subscription_id
+    }
+
+    pub(crate) fn unsubscribe(&mut self, subscription_id: SubscriptionId) {
+        self.callbacks.retain(|(id, _)| *id != subscription_id);
+    }
+}
+
+/// The level of `value`, except that a reading stays at its `current` level, or the highest
+/// level below that it's still near, until it's below the level's threshold by `hysteresis` of
+/// the threshold.
+fn level_with_hysteresis(
+    thresholds: &Thresholds,
+    current: Level,
+    value: f64,
+    hysteresis: f64,
+) -> Level {
+    let level = thresholds.level(value);
+    if level >= current {
+        return level;
+    }
+
+    Level::ALL
+        .into_iter()
+        .rev()
+        .filter(|kept| *kept <= current)
+        .find(|kept| {
+            thresholds
+                .threshold(*kept)
+                .is_none_or(|threshold| value >= threshold - threshold.abs() * hysteresis)
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: Thresholds = Thresholds {
+        warm: 70.0,
+        hot: 85.0,
+        critical: 95.0,
+    };
+    const TRENDS: TrendSettings = TrendSettings {
+        rate: 5.0,
+        period: Duration::from_secs(60),
+    };
+
+    fn alerts(settings: AlertSettings, trends: bool) -> Alerts {
+        let trends = if trends {
+            HashMap::from([(SensorKind::Temp, TRENDS)])
+        } else {
+            HashMap::new()
+        };
+        Alerts::new(
+            &settings,
+            &HashMap::from([(SensorKind::Temp, THRESHOLDS)]),
+            &trends,
+        )
+    }
+
+    fn no_cooldown() -> AlertSettings {
+        AlertSettings {
+            cooldown: Duration::ZERO,
+            ..AlertSettings::default()
+        }
+    }
+
+    /// The causes of the alerts a reading of the aggregate temperature raises.
+    fn check(alerts: &mut Alerts, value: f64, samples: &[Sample]) -> Vec<AlertCause> {
+        let (raised, _) = alerts.check(
+            AlertSource::Aggregate(SensorKind::Temp),
+            "CPU",
+            SensorKind::Temp,
+            value,
+            &Units::Celsius,
+            samples,
+        );
+        raised.into_iter().map(|alert| alert.cause).collect()
+    }
+
+    fn threshold(level: Level) -> AlertCause {
+        AlertCause::Threshold {
+            level,
+            threshold: THRESHOLDS.threshold(level).unwrap(),
+        }
+    }
+
+    /// A reading every 10 seconds for the last minute, changing at `rate` per minute to reach
+    /// `latest`.
+    fn trending(rate: f64, latest: f64) -> Vec<Sample> {
+        let now = Instant::now();
+        (0..=6)
+            .rev()
+            .map(|tens| {
+                let ago = Duration::from_secs(tens * 10);
+                let value = latest - rate * ago.as_secs_f64() / 60.0;
+                Sample {
+                    time: now - ago,
+                    value,
+                    min: value,
+                    max: value,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn alerts_as_readings_rise_into_each_level() {
+        let mut alerts = alerts(no_cooldown(), false);
+
+        assert_eq!(check(&mut alerts, 60.0, &[]), vec![]);
+        assert_eq!(check(&mut alerts, 72.0, &[]), vec![threshold(Level::Warm)]);
+        assert_eq!(check(&mut alerts, 74.0, &[]), vec![]);
+        assert_eq!(
+            check(&mut alerts, 96.0, &[]),
+            vec![threshold(Level::Critical)]
+        );
+        assert_eq!(check(&mut alerts, 97.0, &[]), vec![]);
+    }
+
+    #[test]
+    fn stays_at_a_level_until_well_below_its_threshold() {
+        let mut alerts = alerts(no_cooldown(), false);
+        check(&mut alerts, 96.0, &[]);
+
+        // Still within 5% of 95℃.
+        assert_eq!(check(&mut alerts, 91.0, &[]), vec![]);
+        assert_eq!(check(&mut alerts, 96.0, &[]), vec![]);
+        assert_eq!(check(&mut alerts, 89.0, &[]), vec![]);
+        assert_eq!(
+            check(&mut alerts, 96.0, &[]),
+            vec![threshold(Level::Critical)]
+        );
+    }
+
+    #[test]
+    fn keeps_the_highest_level_still_near() {
+        assert_eq!(
+            level_with_hysteresis(&THRESHOLDS, Level::Critical, 91.0, 0.05),
+            Level::Critical
+        );
+        assert_eq!(
+            level_with_hysteresis(&THRESHOLDS, Level::Critical, 82.0, 0.05),
+            Level::Hot
+        );
+        assert_eq!(
+            level_with_hysteresis(&THRESHOLDS, Level::Critical, 50.0, 0.05),
+            Level::Normal
+        );
+        assert_eq!(
+            level_with_hysteresis(&THRESHOLDS, Level::Warm, 96.0, 0.05),
+            Level::Critical
+        );
+    }
+
+    #[test]
+    fn cools_down_unless_the_level_is_higher() {
+        let mut alerts = alerts(AlertSettings::default(), false);
+        assert_eq!(check(&mut alerts, 72.0, &[]), vec![threshold(Level::Warm)]);
+        check(&mut alerts, 50.0, &[]);
+
+        assert_eq!(check(&mut alerts, 72.0, &[]), vec![]);
+        assert_eq!(check(&mut alerts, 86.0, &[]), vec![threshold(Level::Hot)]);
+    }
+
+    #[test]
+    fn tracks_levels_while_snoozed() {
+        let mut alerts = alerts(no_cooldown(), false);
+        alerts.snooze(SNOOZE);
+        assert_eq!(check(&mut alerts, 96.0, &[]), vec![]);
+
+        alerts.snooze(Duration::ZERO);
+        assert_eq!(check(&mut alerts, 97.0, &[]), vec![]);
+        check(&mut alerts, 50.0, &[]);
+        assert_eq!(
+            check(&mut alerts, 96.0, &[]),
+            vec![threshold(Level::Critical)]
+        );
+    }
+
+    #[test]
+    fn snoozes_one_source_at_a_time() {
+        let mut alerts = alerts(
+            AlertSettings {
+                per_sensor: true,
+                ..no_cooldown()
+            },
+            false,
+        );
+        let gpu = AlertSource::Sensor("/gpu".to_string());
+        alerts.snooze_source(&gpu, SNOOZE);
+
+        let (raised, _) = alerts.check(
+            gpu.clone(),
+            "GPU",
+            SensorKind::Temp,
+            96.0,
+            &Units::Celsius,
+            &[],
+        );
+        assert_eq!(raised, vec![]);
+        assert_eq!(
+            check(&mut alerts, 96.0, &[]),
+            vec![threshold(Level::Critical)]
+        );
+
+        alerts.snooze_source(&gpu, Duration::ZERO);
+        alerts.check(
+            gpu.clone(),
+            "GPU",
+            SensorKind::Temp,
+            50.0,
+            &Units::Celsius,
+            &[],
+        );
+        let (raised, _) = alerts.check(gpu, "GPU", SensorKind::Temp, 96.0, &Units::Celsius, &[]);
+        assert_eq!(raised.len(), 1);
+    }
+
+    #[test]
+    fn only_alerts_about_sensors_when_asked() {
+        for per_sensor in [false, true] {
+            let mut alerts = alerts(
+                AlertSettings {
+                    per_sensor,
+                    ..AlertSettings::default()
+                },
+                false,
+            );
+            let (raised, _) = alerts.check(
+                AlertSource::Sensor("cpu".to_string()),
+                "CPU",
+                SensorKind::Temp,
+                96.0,
+                &Units::Celsius,
+                &[],
+            );
+            assert_eq!(raised.len(), usize::from(per_sensor));
+        }
+    }
+
+    #[test]
+    fn alerts_once_while_rising_steadily() {
+        let mut alerts = alerts(no_cooldown(), true);
+
+        assert_eq!(check(&mut alerts, 50.0, &trending(1.0, 50.0)), vec![]);
+        assert_eq!(
+            check(&mut alerts, 55.0, &trending(6.0, 55.0)),
+            vec![AlertCause::Rising]
+        );
+        assert_eq!(check(&mut alerts, 56.0, &trending(6.0, 56.0)), vec![]);
+        // Within 5% of the rate, so it's still rising.
+        assert_eq!(check(&mut alerts, 57.0, &trending(4.9, 57.0)), vec![]);
+        assert_eq!(check(&mut alerts, 58.0, &trending(5.1, 58.0)), vec![]);
+        assert_eq!(check(&mut alerts, 58.0, &trending(2.0, 58.0)), vec![]);
+        assert_eq!(
+            check(&mut alerts, 60.0, &trending(6.0, 60.0)),
+            vec![AlertCause::Rising]
+        );
+    }
+
+    #[test]
+    fn cools_down_between_rising_alerts() {
+        let mut alerts = alerts(AlertSettings::default(), true);
+
+        assert_eq!(
+            check(&mut alerts, 55.0, &trending(6.0, 55.0)),
+            vec![AlertCause::Rising]
+        );
+        check(&mut alerts, 55.0, &trending(0.0, 55.0));
+        assert_eq!(check(&mut alerts, 60.0, &trending(6.0, 60.0)), vec![]);
+    }
+
+    #[test]
+    fn describes_the_trend_of_each_alert() {
+        let mut alerts = alerts(no_cooldown(), true);
+        let (raised, _) = alerts.check(
+            AlertSource::Aggregate(SensorKind::Temp),
+            "CPU",
+            SensorKind::Temp,
+            80.0,
+            &Units::Celsius,
+            &trending(6.0, 80.0),
+        );
+
+        let causes: Vec<AlertCause> = raised.iter().map(|alert| alert.cause).collect();
+        assert_eq!(causes, vec![threshold(Level::Warm), AlertCause::Rising]);
+        let trend = raised[0].trend.unwrap();
+        assert!((trend.rate - 6.0).abs() < 0.01);
+        assert!(trend.time_to_critical.is_some());
+    }
+}
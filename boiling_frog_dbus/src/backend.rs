@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::alerts::{AlertSource, Alerts};
+#[cfg(feature = "sqlite")]
+use crate::database::DatabaseHandle;
+use crate::error::UnknownSensorError;
+use crate::mutex_helpers::lock;
+use crate::sensor_registry::SensorRegistry;
+use crate::simple_types::{SensorKind, SensorSnapshot};
+use crate::sorted_property_observer::SortedPropertyObserver;
+use crate::units::Units;
+use crate::GenericError;
+#[cfg(feature = "sqlite")]
+use log::error;
+
+/// A sensor found by a [`SensorBackend`]'s discovery step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sensor {
+    /// Uniquely identifies the sensor across all backends, e.g. its D-Bus object or sysfs path.
+    pub id: String,
+    /// Where the sensor is read from, e.g. its D-Bus object path or sysfs file.
+    pub path: String,
+    /// The D-Bus interface or hwmon chip the sensor belongs to.
+    pub interface: String,
+    pub label: String,
+    pub kind: SensorKind,
+    pub units: Units,
+}
+
+/// A source of sensor readings that [`crate::dbus_engine::DbusEngine`] can aggregate.
+pub trait SensorBackend: Send {
+    /// A short name used when logging problems with this backend.
+    fn name(&self) -> &str;
+
+    /// Finds the sensors this backend can report on.
+    fn discover(&mut self) -> Result<Vec<Sensor>, GenericError>;
+
+    /// Whether this backend reads the same sensors as another, so that it should only report
+    /// while no other backend's service is available (see [`SensorSink::is_primary_available`]).
+    /// A fallback backend's discovered sensors aren't added to the sink for it; it adds & removes
+    /// them itself as the primary service comes & goes.
+    fn is_fallback(&self) -> bool {
+        false
+    }
+
+    /// Starts sending readings for the discovered sensors to `sink`. Must not block.
+    ///
+    /// Sensors that appear or go away after discovery can be added to or removed from `sink`.
+    fn start(&mut self, sink: SensorSink) -> Result<(), GenericError>;
+
+    /// Stops sending readings and releases any threads or connections the backend holds.
+    fn shutdown(&mut self) -> Result<(), GenericError>;
+}
+
+/// Receives the `(sensor id, value, units)` updates from a running [`SensorBackend`], along with
+/// any sensors it adds or removes at runtime. Readings from sensors that have been filtered out
+/// are kept, but not aggregated.
+#[derive(Clone)]
+pub struct SensorSink {
+    alerts: Arc<Mutex<Alerts>>,
+    #[cfg(feature = "sqlite")]
+    database: Option<DatabaseHandle>,
+    /// Whether a backend that isn't a fallback has its service running.
+    primary_available: Arc<AtomicBool>,
+    registry: Arc<Mutex<SensorRegistry>>,
+    observers: HashMap<SensorKind, SortedPropertyObserver>,
+}
+
+impl SensorSink {
+    pub(crate) fn new(
+        alerts: &Arc<Mutex<Alerts>>,
+        registry: &Arc<Mutex<SensorRegistry>>,
+        observers: &HashMap<SensorKind, SortedPropertyObserver>,
+        #[cfg(feature = "sqlite")] database: Option<&DatabaseHandle>,
+    ) -> SensorSink {
+        SensorSink {
+            alerts: alerts.clone(),
+            #[cfg(feature = "sqlite")]
+            database: database.cloned(),
+            primary_available: Arc::new(AtomicBool::new(false)),
+            registry: registry.clone(),
+            observers: observers.clone(),
+        }
+    }
+
+    /// Tells the fallback backends whether the service this backend reads from is running, e.g.
+    /// whether Indicator Sensors has an owner on the bus.
+    pub fn set_primary_available(&self, available: bool) {
+        self.primary_available.store(available, Ordering::SeqCst);
+    }
+
+    /// Whether a fallback backend should stand aside, because another backend is reading the
+    /// sensors.
+    pub fn is_primary_available(&self) -> bool {
+        self.primary_available.load(Ordering::SeqCst)
+    }
+
+    pub fn add(&self, sensor: Sensor) -> Result<(), GenericError> {
+        let mut registry = lock(&self.registry)?;
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            let retention = registry.history_limits().retention;
+            if let Some(history) = registry.start_history(&sensor.id) {
+                if let Err(e) = database.preload(&sensor.id, history, retention) {
+                    error!("Could not load the stored readings of {}: {e}", sensor.id);
+                }
+            }
+        }
+        registry.add(Sensor {
+            units: sensor.units.canonical(),
+            ..sensor
+        });
+        Ok(())
+    }
+
+    /// Forgets the sensor with `id`, so that its last reading no longer counts.
+    pub fn remove(&self, id: &str) -> Result<(), GenericError> {
+        let snapshot = lock(&self.registry)?.remove(id);
+        lock(&self.alerts)?.forget(&AlertSource::Sensor(id.to_string()));
+        if let Some(observer) = snapshot.and_then(|snapshot| self.observers.get(&snapshot.kind)) {
+            observer.remove(id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Records a reading, converting it to [`Units::canonical`] units so that it can be compared
+    /// with readings in other units.
+    pub fn update(&self, id: &str, value: f64, units: &Units) -> Result<(), GenericError> {
+        let value = units.to_canonical(value);
+        let units = &units.canonical();
+        // Callbacks run without the registry locked, so that they can query the engine.
+        let (snapshot, callbacks) = {
+            let mut registry = lock(&self.registry)?;
+            let update = registry
+                .update(id, value, units)
+                .ok_or(UnknownSensorError { id: id.to_string() })?;
+            if registry.is_excluded(id) {
+                return Ok(());
+            }
+            update
+        };
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            database.record(id, &snapshot.label, snapshot.kind, value, units);
+        }
+        if let Some(observer) = self.observers.get(&snapshot.kind) {
+            observer.update(id, &snapshot.label, value, units, snapshot.updated)?;
+        }
+        self.check_alerts(&snapshot, value, units)?;
+        for callback in callbacks {
+            lock(&callback)?(snapshot.clone())?;
+        }
+
+        Ok(())
+    }
+
+    fn check_alerts(
+        &self,
+        snapshot: &SensorSnapshot,
+        value: f64,
+        units: &Units,
+    ) -> Result<(), GenericError> {
+        // The registry & the alerts are never locked together, because setting the filter locks
+        // the alerts with the registry locked.
+        let source = AlertSource::Sensor(snapshot.id.clone());
+        let period = {
+            let alerts = lock(&self.alerts)?;
+            if !alerts.is_checked(&source, snapshot.kind) {
+                return Ok(());
+            }
+            alerts.trend_period(snapshot.kind)
+        };
+        let samples = match (period, snapshot.updated) {
+            (Some(period), Some(now)) => lock(&self.registry)?
+                .history(&snapshot.id, now.checked_sub(period).unwrap_or(now), now)
+                .unwrap_or_default(),
+            _ => vec![],
+        };
+        let (alerts, callbacks) = lock(&self.alerts)?.check(
+            source,
+            &snapshot.label,
+            snapshot.kind,
+            value,
+            units,
+            &samples,
+        );
+        for alert in alerts {
+            for callback in &callbacks {
+                lock(callback)?(alert.clone())?;
+            }
+        }
+
+        Ok(())
+    }
+}
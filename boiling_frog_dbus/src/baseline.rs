@@ -0,0 +1,637 @@
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read_to_string, rename, write};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::config::PROC_STAT_PATH;
+use crate::error::ThreadJoinError;
+use crate::mutex_helpers::lock;
+use crate::sensor_registry::SensorRegistry;
+use crate::simple_types::SensorKind;
+use crate::GenericError;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// How many days of readings are kept.
+const RETENTION_DAYS: u64 = 120;
+/// The width of the fan speed bands that temperatures are compared within, in RPM.
+pub const FAN_BAND: u32 = 500;
+/// The CPU utilisation up to which the machine counts as idle, in %.
+const IDLE_UP_TO: f64 = 20.0;
+/// The CPU utilisation from which the machine counts as fully loaded, in %.
+const FULL_LOAD_FROM: f64 = 80.0;
+/// How often readings are added to the baseline.
+const SAMPLE_EVERY: Duration = Duration::from_secs(60);
+/// How often the baseline is saved while readings are being added to it.
+const SAVE_EVERY: Duration = Duration::from_secs(10 * 60);
+
+/// How busy the machine is, going by its CPU utilisation.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Load {
+    Idle,
+    Full,
+}
+
+/// The conditions that readings are compared under.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    /// The aggregate fan speed was in the [`FAN_BAND`] starting at this speed, in RPM.
+    FanSpeed(u32),
+    Load(Load),
+}
+
+/// How the average of a kind of reading under the same conditions has changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Drift {
+    pub condition: Condition,
+    /// [`SensorKind::Temp`] or [`SensorKind::Fan`].
+    pub kind: SensorKind,
+    /// In canonical units.
+    pub then: f64,
+    pub now: f64,
+}
+
+impl Drift {
+    /// How much higher the reading is now, which is negative if it's lower.
+    pub fn change(&self) -> f64 {
+        self.now - self.then
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+struct Mean {
+    sum: f64,
+    count: u64,
+}
+
+impl Mean {
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Mean) {
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    fn value(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// The readings from one day.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+struct Day {
+    /// Temperatures keyed by the lowest speed of the [`FAN_BAND`] the aggregate fan speed was in.
+    temp_by_fan: BTreeMap<u32, Mean>,
+    temp_by_load: BTreeMap<Load, Mean>,
+    fan_by_load: BTreeMap<Load, Mean>,
+}
+
+impl Day {
+    fn merge(&mut self, other: &Day) {
+        for (band, mean) in &other.temp_by_fan {
+            self.temp_by_fan.entry(*band).or_default().merge(mean);
+        }
+        for (load, mean) in &other.temp_by_load {
+            self.temp_by_load.entry(*load).or_default().merge(mean);
+        }
+        for (load, mean) in &other.fan_by_load {
+            self.fan_by_load.entry(*load).or_default().merge(mean);
+        }
+    }
+}
+
+/// How hot the machine runs & how fast its fans spin under the same conditions, kept for months
+/// so that slow changes such as dust building up or thermal paste drying out show.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Baseline {
+    /// Keyed by days since the Unix epoch.
+    days: BTreeMap<u64, Day>,
+}
+
+impl Baseline {
+    /// Reads a baseline written by [`Baseline::save`], or starts an empty one if there's no file
+    /// at `path`.
+    pub fn load(path: &Path) -> Result<Baseline, GenericError> {
+        match read_to_string(path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Baseline::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Writes the baseline to `path` all at once, so that it's never left half-written.
+    pub fn save(&self, path: &Path) -> Result<(), GenericError> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let partial = path.with_extension("partial");
+        write(&partial, serde_json::to_string(self)?)?;
+        rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// When the earliest readings were recorded.
+    pub fn since(&self) -> Option<SystemTime> {
+        let day = self.days.keys().next()?;
+        UNIX_EPOCH.checked_add(Duration::from_secs(day * SECONDS_PER_DAY))
+    }
+
+    /// Adds the highest temperature & fan speed, in canonical units, & the CPU utilisation in %
+    /// at `time`, returning whether there was a condition to add them under.
+    pub fn record(
+        &mut self,
+        time: SystemTime,
+        temp: f64,
+        fan: Option<f64>,
+        utilisation: Option<f64>,
+    ) -> bool {
+        let fan = fan.filter(|fan| *fan >= 0.0);
+        let load = utilisation.and_then(|utilisation| {
+            if utilisation <= IDLE_UP_TO {
+                Some(Load::Idle)
+            } else if utilisation >= FULL_LOAD_FROM {
+                Some(Load::Full)
+            } else {
+                None
+            }
+        });
+        if fan.is_none() && load.is_none() {
+            return false;
+        }
+
+        let today = day_number(time);
+        let day = self.days.entry(today).or_default();
+        if let Some(fan) = fan {
+            let band = (fan as u32 / FAN_BAND) * FAN_BAND;
+            day.temp_by_fan.entry(band).or_default().add(temp);
+        }
+        if let Some(load) = load {
+            day.temp_by_load.entry(load).or_default().add(temp);
+            if let Some(fan) = fan {
+                day.fan_by_load.entry(load).or_default().add(fan);
+            }
+        }
+
+        self.forget_old(today);
+        true
+    }
+
+    /// Adds the readings in `other`, e.g. those another process has saved meanwhile.
+    pub fn merge(&mut self, other: &Baseline) {
+        for (number, day) in &other.days {
+            self.days.entry(*number).or_default().merge(day);
+        }
+    }
+
+    /// Compares the readings from the last `period` up to `now` with those from the same length
+    /// of period `age` earlier, under each condition with at least `min_samples` readings in
+    /// both. Readings are sampled once a minute.
+    pub fn compare(
+        &self,
+        now: SystemTime,
+        period: Duration,
+        age: Duration,
+        min_samples: u64,
+    ) -> Vec<Drift> {
+        let period_days = (period.as_secs() / SECONDS_PER_DAY).max(1);
+        let today = day_number(now);
+        let recent = self.merged((today + 1).saturating_sub(period_days), today);
+        let then_end = today.saturating_sub(age.as_secs() / SECONDS_PER_DAY);
+        let then = self.merged((then_end + 1).saturating_sub(period_days), then_end);
+
+        let mut drifts = vec![];
+        let mut compare =
+            |condition: Condition, kind: SensorKind, then: Option<&Mean>, now: Option<&Mean>| {
+                let enough = |mean: &&Mean| mean.count >= min_samples;
+                if let (Some(then), Some(now)) = (
+                    then.filter(enough).and_then(Mean::value),
+                    now.filter(enough).and_then(Mean::value),
+                ) {
+                    drifts.push(Drift {
+                        condition,
+                        kind,
+                        then,
+                        now,
+                    });
+                }
+            };
+        for (band, mean) in &recent.temp_by_fan {
+            compare(
+                Condition::FanSpeed(*band),
+                SensorKind::Temp,
+                then.temp_by_fan.get(band),
+                Some(mean),
+            );
+        }
+        for (load, mean) in &recent.temp_by_load {
+            compare(
+                Condition::Load(*load),
+                SensorKind::Temp,
+                then.temp_by_load.get(load),
+                Some(mean),
+            );
+        }
+        for (load, mean) in &recent.fan_by_load {
+            compare(
+                Condition::Load(*load),
+                SensorKind::Fan,
+                then.fan_by_load.get(load),
+                Some(mean),
+            );
+        }
+
+        drifts
+    }
+
+    /// Forgets the days that are older than [`RETENTION_DAYS`] by `today`.
+    fn forget_old(&mut self, today: u64) {
+        self.days = self
+            .days
+            .split_off(&today.saturating_sub(RETENTION_DAYS - 1));
+    }
+
+    /// All the readings from the `first` to the `last` day.
+    fn merged(&self, first: u64, last: u64) -> Day {
+        let mut merged = Day::default();
+        for day in self.days.range(first..=last).map(|(_, day)| day) {
+            merged.merge(day);
+        }
+        merged
+    }
+}
+
+/// Adds readings to a [`Baseline`] & saves them every so often. Saving adds them to what's in the
+/// file then, so that several processes can record the same baseline.
+pub(crate) struct BaselineRecorder {
+    baseline: Baseline,
+    last_save: Instant,
+    path: PathBuf,
+    /// The readings added since the baseline was last saved.
+    unsaved: Baseline,
+}
+
+impl BaselineRecorder {
+    /// Carries on from the baseline saved at `path`.
+    pub(crate) fn load(path: &Path) -> Result<BaselineRecorder, GenericError> {
+        Ok(BaselineRecorder {
+            baseline: Baseline::load(path)?,
+            last_save: Instant::now(),
+            path: path.to_path_buf(),
+            unsaved: Baseline::default(),
+        })
+    }
+
+    pub(crate) fn baseline(&self) -> &Baseline {
+        &self.baseline
+    }
+
+    pub(crate) fn record(&mut self, temp: f64, fan: Option<f64>, utilisation: Option<f64>) {
+        let time = SystemTime::now();
+        if !self.unsaved.record(time, temp, fan, utilisation) {
+            return;
+        }
+        self.baseline.record(time, temp, fan, utilisation);
+
+        if self.last_save.elapsed() >= SAVE_EVERY {
+            self.save();
+        }
+    }
+
+    /// Saves the unsaved readings, logging rather than returning any error so that readings
+    /// carry on. They're kept to try again next time if they can't be saved.
+    pub(crate) fn save(&mut self) {
+        self.last_save = Instant::now();
+        if self.unsaved.days.is_empty() {
+            return;
+        }
+        let saved = Baseline::load(&self.path).and_then(|mut saved| {
+            saved.merge(&self.unsaved);
+            saved.forget_old(day_number(SystemTime::now()));
+            saved.save(&self.path)?;
+            Ok(saved)
+        });
+        match saved {
+            Ok(saved) => {
+                self.baseline = saved;
+                self.unsaved = Baseline::default();
+            }
+            Err(e) => error!(
+                "Could not save the baseline to {}: {e}",
+                self.path.display()
+            ),
+        }
+    }
+}
+
+/// Samples the highest temperature & fan speed of all the sensors, & how busy the CPU has been,
+/// once a minute on its own thread. They're the same whichever backend reads the sensors &
+/// however the panels aggregate them, so that the baseline stays comparable.
+pub(crate) struct BaselineSampler {
+    recorder: Arc<Mutex<BaselineRecorder>>,
+    stop: Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BaselineSampler {
+    pub(crate) fn start(
+        recorder: BaselineRecorder,
+        registry: &Arc<Mutex<SensorRegistry>>,
+    ) -> Result<BaselineSampler, GenericError> {
+        let recorder = Arc::new(Mutex::new(recorder));
+        let (stop, stopped) = channel();
+        let thread_recorder = recorder.clone();
+        let registry = registry.clone();
+        let thread = thread::Builder::new()
+            .name("baseline".to_string())
+            .spawn(move || {
+                let mut cpu = CpuTimes::read(Path::new(PROC_STAT_PATH));
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(SAMPLE_EVERY) {
+                    let now = CpuTimes::read(Path::new(PROC_STAT_PATH));
+                    let utilisation = match (&cpu, &now) {
+                        (Some(then), Some(now)) => now.utilisation_since(then),
+                        _ => None,
+                    };
+                    cpu = now;
+                    if let Err(e) = sample(&thread_recorder, &registry, utilisation) {
+                        error!("Could not sample the baseline: {e}");
+                    }
+                }
+            })?;
+
+        Ok(BaselineSampler {
+            recorder,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub(crate) fn baseline(&self) -> Result<Baseline, GenericError> {
+        Ok(lock(&self.recorder)?.baseline().clone())
+    }
+
+    pub(crate) fn save(&self) -> Result<(), GenericError> {
+        lock(&self.recorder)?.save();
+        Ok(())
+    }
+
+    /// Stops sampling & saves what hasn't been saved yet.
+    pub(crate) fn shutdown(&mut self) -> Result<(), GenericError> {
+        // The thread only goes away once it's been stopped.
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            thread.join().map_err(|_| ThreadJoinError {
+                name: "baseline".to_string(),
+            })?;
+        }
+        self.save()
+    }
+}
+
+/// Adds the highest temperature & fan speed the sensors are reading to the baseline.
+fn sample(
+    recorder: &Arc<Mutex<BaselineRecorder>>,
+    registry: &Arc<Mutex<SensorRegistry>>,
+    utilisation: Option<f64>,
+) -> Result<(), GenericError> {
+    let snapshots = lock(registry)?.snapshots();
+    let highest = |kind: SensorKind| {
+        snapshots
+            .iter()
+            .filter(|snapshot| snapshot.kind == kind)
+            .filter_map(|snapshot| snapshot.value)
+            .max_by(f64::total_cmp)
+    };
+    if let Some(temp) = highest(SensorKind::Temp) {
+        lock(recorder)?.record(temp, highest(SensorKind::Fan), utilisation);
+    }
+    Ok(())
+}
+
+/// The time the CPUs have spent busy & in all, from the first line of `/proc/stat`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    fn read(path: &Path) -> Option<CpuTimes> {
+        CpuTimes::parse(&read_to_string(path).ok()?)
+    }
+
+    /// Parses e.g. "cpu  4705 150 1120 16250 520 0 30 0 0 0", where the 4th & 5th times are idle
+    /// & waiting for IO.
+    fn parse(stat: &str) -> Option<CpuTimes> {
+        let mut fields = stat.lines().next()?.split_whitespace();
+        if fields.next()? != "cpu" {
+            return None;
+        }
+        let times = fields
+            .map(str::parse::<u64>)
+            .collect::<Result<Vec<u64>, _>>()
+            .ok()?;
+        let idle = times.get(3)? + times.get(4).unwrap_or(&0);
+        // Guest time is also counted in user time.
+        let total: u64 = times.iter().take(8).sum();
+        Some(CpuTimes {
+            busy: total.saturating_sub(idle),
+            total,
+        })
+    }
+
+    /// How busy the CPUs have been since `earlier`, in %.
+    fn utilisation_since(&self, earlier: &CpuTimes) -> Option<f64> {
+        let total = self
+            .total
+            .checked_sub(earlier.total)
+            .filter(|total| *total > 0)?;
+        let busy = self.busy.saturating_sub(earlier.busy);
+        Some(busy as f64 / total as f64 * 100.0)
+    }
+}
+
+fn day_number(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() / SECONDS_PER_DAY)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(SECONDS_PER_DAY);
+
+    fn day(number: u32) -> SystemTime {
+        UNIX_EPOCH + DAY * number + Duration::from_secs(12 * 60 * 60)
+    }
+
+    /// Records `count` readings on day `number`.
+    fn record(
+        baseline: &mut Baseline,
+        number: u32,
+        count: usize,
+        temp: f64,
+        fan: Option<f64>,
+        utilisation: Option<f64>,
+    ) {
+        for _ in 0..count {
+            assert!(baseline.record(day(number), temp, fan, utilisation));
+        }
+    }
+
+    #[test]
+    fn needs_a_fan_speed_or_a_clear_load_to_record() {
+        let mut baseline = Baseline::default();
+
+        assert!(!baseline.record(day(1000), 50.0, None, None));
+        assert!(!baseline.record(day(1000), 50.0, None, Some(50.0)));
+        assert!(!baseline.record(day(1000), 50.0, Some(-1.0), None));
+        assert_eq!(baseline.since(), None);
+        assert!(baseline.record(day(1000), 50.0, None, Some(10.0)));
+        assert!(baseline.record(day(1001), 50.0, Some(0.0), None));
+        assert_eq!(baseline.since(), Some(UNIX_EPOCH + DAY * 1000));
+    }
+
+    #[test]
+    fn compares_recent_readings_with_older_ones_under_the_same_conditions() {
+        let mut baseline = Baseline::default();
+        // A month ago & in the last week.
+        record(&mut baseline, 962, 10, 60.0, Some(2100.0), Some(10.0));
+        record(&mut baseline, 963, 10, 80.0, Some(3000.0), Some(90.0));
+        record(&mut baseline, 995, 10, 66.0, Some(2400.0), Some(10.0));
+        record(&mut baseline, 996, 10, 84.0, Some(3400.0), Some(90.0));
+        // Outside both periods.
+        record(&mut baseline, 980, 10, 100.0, Some(2200.0), Some(10.0));
+
+        let drifts = baseline.compare(day(997), DAY * 7, DAY * 30, 10);
+        assert_eq!(
+            drifts,
+            vec![
+                Drift {
+                    condition: Condition::FanSpeed(2000),
+                    kind: SensorKind::Temp,
+                    then: 60.0,
+                    now: 66.0,
+                },
+                Drift {
+                    condition: Condition::FanSpeed(3000),
+                    kind: SensorKind::Temp,
+                    then: 80.0,
+                    now: 84.0,
+                },
+                Drift {
+                    condition: Condition::Load(Load::Idle),
+                    kind: SensorKind::Temp,
+                    then: 60.0,
+                    now: 66.0,
+                },
+                Drift {
+                    condition: Condition::Load(Load::Full),
+                    kind: SensorKind::Temp,
+                    then: 80.0,
+                    now: 84.0,
+                },
+                Drift {
+                    condition: Condition::Load(Load::Idle),
+                    kind: SensorKind::Fan,
+                    then: 2100.0,
+                    now: 2400.0,
+                },
+                Drift {
+                    condition: Condition::Load(Load::Full),
+                    kind: SensorKind::Fan,
+                    then: 3000.0,
+                    now: 3400.0,
+                },
+            ]
+        );
+        assert_eq!(drifts[0].change(), 6.0);
+    }
+
+    #[test]
+    fn only_compares_conditions_with_enough_samples_in_both_periods() {
+        let mut baseline = Baseline::default();
+        record(&mut baseline, 962, 10, 60.0, Some(2100.0), None);
+        record(&mut baseline, 962, 5, 60.0, Some(4100.0), None);
+        record(&mut baseline, 995, 10, 66.0, Some(2400.0), None);
+        record(&mut baseline, 995, 10, 66.0, Some(4200.0), None);
+        record(&mut baseline, 995, 10, 66.0, Some(5200.0), None);
+
+        let conditions: Vec<Condition> = baseline
+            .compare(day(997), DAY * 7, DAY * 30, 10)
+            .iter()
+            .map(|drift| drift.condition)
+            .collect();
+        assert_eq!(conditions, vec![Condition::FanSpeed(2000)]);
+    }
+
+    #[test]
+    fn merges_the_readings_saved_elsewhere() {
+        let mut baseline = Baseline::default();
+        let mut other = Baseline::default();
+        record(&mut baseline, 962, 5, 60.0, Some(2100.0), None);
+        record(&mut other, 962, 5, 70.0, Some(2100.0), None);
+        record(&mut baseline, 995, 10, 66.0, Some(2100.0), None);
+        baseline.merge(&other);
+
+        let drifts = baseline.compare(day(997), DAY * 7, DAY * 30, 10);
+        assert_eq!(drifts.len(), 1);
+        assert_eq!(drifts[0].then, 65.0);
+    }
+
+    #[test]
+    fn forgets_readings_older_than_retention() {
+        let mut baseline = Baseline::default();
+        record(&mut baseline, 1000, 1, 60.0, Some(2100.0), None);
+        record(&mut baseline, 1001, 1, 60.0, Some(2100.0), None);
+        record(
+            &mut baseline,
+            1000 + RETENTION_DAYS as u32,
+            1,
+            60.0,
+            Some(2100.0),
+            None,
+        );
+
+        assert_eq!(baseline.since(), Some(UNIX_EPOCH + DAY * 1001));
+    }
+
+    #[test]
+    fn saves_and_loads_the_baseline() {
+        let directory = tempfile::TempDir::new().unwrap();
+        let path = directory.path().join("nested").join("baseline.json");
+        let mut baseline = Baseline::load(&path).unwrap();
+        record(&mut baseline, 1000, 3, 60.0, Some(2100.0), Some(90.0));
+        baseline.save(&path).unwrap();
+
+        assert_eq!(Baseline::load(&path).unwrap(), baseline);
+    }
+
+    #[test]
+    fn works_out_cpu_utilisation_from_proc_stat() {
+        let then = CpuTimes::parse("cpu  100 0 100 700 100 0 0 0 0 0\ncpu0 1 2 3 4\n").unwrap();
+        let now = CpuTimes::parse("cpu  250 0 150 900 100 0 0 0 0 0\n").unwrap();
+
+        assert_eq!(
+            then,
+            CpuTimes {
+                busy: 200,
+                total: 1000,
+            }
+        );
+        assert_eq!(now.utilisation_since(&then), Some(50.0));
+        assert_eq!(then.utilisation_since(&then), None);
+        assert_eq!(CpuTimes::parse("intr 1 2 3\n"), None);
+        assert_eq!(CpuTimes::parse(""), None);
+    }
+}
@@ -1,2 +1,20 @@
+use std::time::Duration;
+
 pub const INDICATOR_SENSORS_SERVICE: &str = "com.github.alexmurray.IndicatorSensors";
 pub const ACTIVE_SENSORS_PATH: &str = "/com/github/alexmurray/IndicatorSensors/ActiveSensors";
+#[cfg(feature = "dbus")]
+pub const NOTIFICATIONS_SERVICE: &str = "org.freedesktop.Notifications";
+#[cfg(feature = "dbus")]
+pub const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+#[cfg(feature = "hwmon")]
+pub const HWMON_PATH: &str = "/sys/class/hwmon";
+#[cfg(feature = "hwmon")]
+pub const HWMON_POLL_RATE: Duration = Duration::from_secs(1);
+/// How often Indicator Sensors' latest values are recorded again, as it only signals the ones
+/// that change.
+#[cfg(feature = "dbus")]
+pub const DBUS_REFRESH_RATE: Duration = Duration::from_secs(5);
+/// Where the kernel reports how long the CPUs have been busy, which the baseline's load comes from.
+pub const PROC_STAT_PATH: &str = "/proc/stat";
+/// How long a reading can go without an update before it's considered stale.
+pub const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(30);
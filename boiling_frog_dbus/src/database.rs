@@ -0,0 +1,661 @@
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::error;
+use rusqlite::{params, Connection};
+
+use crate::error::ThreadJoinError;
+use crate::history::History;
+use crate::mutex_helpers::lock;
+use crate::simple_types::SensorKind;
+use crate::units::Units;
+use crate::GenericError;
+
+const MINUTE_MILLIS: i64 = 60 * 1000;
+const HOUR_MILLIS: i64 = 60 * MINUTE_MILLIS;
+/// How often readings that are past their [`Retention`] are deleted.
+const FORGET_EVERY: Duration = Duration::from_secs(60 * 60);
+
+const SCHEMA: &str = "
+PRAGMA journal_mode = WAL;
+CREATE TABLE IF NOT EXISTS sensors (
+    id INTEGER PRIMARY KEY,
+    sensor TEXT NOT NULL UNIQUE,
+    label TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    units TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS readings (
+    sensor INTEGER NOT NULL REFERENCES sensors (id),
+    time INTEGER NOT NULL,
+    value REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS readings_by_sensor ON readings (sensor, time);
+CREATE TABLE IF NOT EXISTS minutes (
+    sensor INTEGER NOT NULL REFERENCES sensors (id),
+    start INTEGER NOT NULL,
+    min REAL NOT NULL,
+    max REAL NOT NULL,
+    sum REAL NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (sensor, start)
+) WITHOUT ROWID;
+CREATE TABLE IF NOT EXISTS hours (
+    sensor INTEGER NOT NULL REFERENCES sensors (id),
+    start INTEGER NOT NULL,
+    min REAL NOT NULL,
+    max REAL NOT NULL,
+    sum REAL NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (sensor, start)
+) WITHOUT ROWID;
+";
+
+/// How long a [`Database`] keeps readings at each [`Resolution`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Retention {
+    /// Defaults to 7 days.
+    pub readings: Duration,
+    /// Defaults to 90 days.
+    pub minutes: Duration,
+    /// Defaults to 2 years.
+    pub hours: Duration,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        let day = 24 * 60 * 60;
+        Retention {
+            readings: Duration::from_secs(7 * day),
+            minutes: Duration::from_secs(90 * day),
+            hours: Duration::from_secs(730 * day),
+        }
+    }
+}
+
+/// Where & how [`crate::DbusEngine`] records readings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatabaseSettings {
+    pub path: PathBuf,
+    pub retention: Retention,
+    /// How long readings are collected for before they're written together. Defaults to 5
+    /// seconds.
+    pub batch_every: Duration,
+}
+
+impl DatabaseSettings {
+    pub fn new(path: &Path) -> DatabaseSettings {
+        DatabaseSettings {
+            path: path.to_path_buf(),
+            retention: Retention::default(),
+            batch_every: Duration::from_secs(5),
+        }
+    }
+}
+
+/// How finely a [`Database`] returns readings: each one, or their minimum, mean & maximum over
+/// each minute or hour.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resolution {
+    Reading,
+    Minute,
+    Hour,
+}
+
+impl Resolution {
+    fn table(&self) -> &'static str {
+        match self {
+            Resolution::Reading => "readings",
+            Resolution::Minute => "minutes",
+            Resolution::Hour => "hours",
+        }
+    }
+}
+
+/// A reading, or the readings from a minute or an hour, in canonical units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StoredSample {
+    /// When the reading was taken, or when the minute or hour started.
+    pub time: SystemTime,
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+    pub count: u64,
+}
+
+/// A sensor with readings in a [`Database`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredSensor {
+    /// The sensor's id, or an [`aggregate_id`].
+    pub id: String,
+    /// The label the sensor last had.
+    pub label: String,
+    pub kind: SensorKind,
+    pub units: Units,
+}
+
+/// A reading on its way to a [`Database`].
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StoredReading {
+    pub(crate) id: String,
+    pub(crate) label: String,
+    pub(crate) kind: SensorKind,
+    pub(crate) units: Units,
+    pub(crate) time: SystemTime,
+    pub(crate) value: f64,
+}
+
+/// The id the aggregate readings of `kind` are stored under.
+pub fn aggregate_id(kind: SensorKind) -> String {
+    format!("aggregate:{}", kind.name())
+}
+
+/// Readings stored in SQLite, each one for a while & rolled up into minutes & hours for longer.
+pub struct Database {
+    connection: Connection,
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> Result<Database, GenericError> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path)?;
+        // The recorder writes while the engine reads.
+        connection.busy_timeout(Duration::from_secs(5))?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Database { connection })
+    }
+
+    /// Every sensor with readings stored, ordered by id.
+    pub fn sensors(&self) -> Result<Vec<StoredSensor>, GenericError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT sensor, label, kind, units FROM sensors ORDER BY sensor")?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        let mut sensors = vec![];
+        for row in rows {
+            let (id, label, kind, units) = row?;
+            sensors.push(StoredSensor {
+                id,
+                label,
+                kind: SensorKind::ALL
+                    .into_iter()
+                    .find(|known| known.name() == kind)
+                    .unwrap_or(SensorKind::Other),
+                units: Units::from(units.as_str()),
+            });
+        }
+        Ok(sensors)
+    }
+
+    /// The samples of the sensor with `id` from `from` to `to`, oldest first.
+    pub fn samples(
+        &self,
+        id: &str,
+        from: SystemTime,
+        to: SystemTime,
+        resolution: Resolution,
+    ) -> Result<Vec<StoredSample>, GenericError> {
+        let sql = match resolution {
+            Resolution::Reading => "SELECT time, value, value, value, 1 FROM readings \
+                 WHERE sensor = (SELECT id FROM sensors WHERE sensor = ?1) \
+                 AND time >= ?2 AND time <= ?3 ORDER BY time"
+                .to_string(),
+            _ => format!(
+                "SELECT start, min, sum / count, max, count FROM {} \
+                 WHERE sensor = (SELECT id FROM sensors WHERE sensor = ?1) \
+                 AND start >= ?2 AND start <= ?3 ORDER BY start",
+                resolution.table()
+            ),
+        };
+        let mut statement = self.connection.prepare_cached(&sql)?;
+        let rows = statement.query_map(params![id, millis(from), millis(to)], |row| {
+            Ok(StoredSample {
+                time: system_time(row.get(0)?),
+                min: row.get(1)?,
+                mean: row.get(2)?,
+                max: row.get(3)?,
+                count: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<StoredSample>, rusqlite::Error>>()?)
+    }
+
+    /// Writes `readings` in one transaction, adding them to their minutes & hours.
+    fn insert(&mut self, readings: &[StoredReading]) -> Result<(), GenericError> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut sensor = transaction.prepare_cached(
+                "INSERT INTO sensors (sensor, label, kind, units) VALUES (?1, ?2, ?3, ?4) \
+                 ON CONFLICT (sensor) DO UPDATE SET label = excluded.label, \
+                 kind = excluded.kind, units = excluded.units \
+                 RETURNING id",
+            )?;
+            let mut reading = transaction
+                .prepare_cached("INSERT INTO readings (sensor, time, value) VALUES (?1, ?2, ?3)")?;
+            let mut minute = transaction.prepare_cached(&rollup_sql(Resolution::Minute))?;
+            let mut hour = transaction.prepare_cached(&rollup_sql(Resolution::Hour))?;
+            let mut last_sensor: Option<(&str, &str, i64)> = None;
+            for stored in readings {
+                let row_id = match last_sensor {
+                    Some((id, label, row_id)) if id == stored.id && label == stored.label => row_id,
+                    _ => sensor.query_row(
+                        params![
+                            stored.id,
+                            stored.label,
+                            stored.kind.name(),
+                            stored.units.symbol()
+                        ],
+                        |row| row.get(0),
+                    )?,
+                };
+                last_sensor = Some((&stored.id, &stored.label, row_id));
+
+                let time = millis(stored.time);
+                reading.execute(params![row_id, time, stored.value])?;
+                minute.execute(params![row_id, time - time % MINUTE_MILLIS, stored.value])?;
+                hour.execute(params![row_id, time - time % HOUR_MILLIS, stored.value])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Deletes the readings, minutes & hours older than `retention` allows.
+    fn forget_old(&self, retention: &Retention, now: SystemTime) -> Result<(), GenericError> {
+        let limits = [
+            (Resolution::Reading, retention.readings),
+            (Resolution::Minute, retention.minutes),
+            (Resolution::Hour, retention.hours),
+        ];
+        for (resolution, kept) in limits {
+            let column = match resolution {
+                Resolution::Reading => "time",
+                _ => "start",
+            };
+            let before = millis(now.checked_sub(kept).unwrap_or(UNIX_EPOCH));
+            self.connection.execute(
+                &format!("DELETE FROM {} WHERE {column} < ?1", resolution.table()),
+                params![before],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What a [`DatabaseRecorder`]'s thread is sent.
+pub(crate) enum Record {
+    Reading(StoredReading),
+    /// Writes the readings still waiting & stops the thread.
+    Stop,
+}
+
+/// Writes the readings it's sent to a [`Database`] in batches, on its own thread.
+pub(crate) struct DatabaseRecorder {
+    sender: Sender<Record>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DatabaseRecorder {
+    pub(crate) fn start(settings: &DatabaseSettings) -> Result<DatabaseRecorder, GenericError> {
+        // Opened here so that a database that can't be opened is reported straight away.
+        let mut database = Database::open(&settings.path)?;
+        let (sender, receiver) = channel();
+        let settings = settings.clone();
+        let thread = thread::Builder::new()
+            .name("database".to_string())
+            .spawn(move || {
+                let mut batch = vec![];
+                let mut last_write = Instant::now();
+                let mut last_forget: Option<Instant> = None;
+                loop {
+                    let timeout = settings.batch_every.saturating_sub(last_write.elapsed());
+                    let is_stopping = match receiver.recv_timeout(timeout) {
+                        Ok(Record::Reading(reading)) => {
+                            batch.push(reading);
+                            false
+                        }
+                        Err(RecvTimeoutError::Timeout) => false,
+                        Ok(Record::Stop) | Err(RecvTimeoutError::Disconnected) => true,
+                    };
+                    if last_write.elapsed() < settings.batch_every && !is_stopping {
+                        continue;
+                    }
+
+                    last_write = Instant::now();
+                    if !batch.is_empty() {
+                        if let Err(e) = database.insert(&batch) {
+                            error!("Could not record {} readings: {e}", batch.len());
+                        }
+                        batch.clear();
+                    }
+                    if last_forget.is_none_or(|last| last.elapsed() >= FORGET_EVERY) {
+                        last_forget = Some(Instant::now());
+                        if let Err(e) = database.forget_old(&settings.retention, SystemTime::now())
+                        {
+                            error!("Could not delete old readings: {e}");
+                        }
+                    }
+                    if is_stopping {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(DatabaseRecorder {
+            sender,
+            thread: Some(thread),
+        })
+    }
+
+    /// Records readings through this recorder & reads them back from `database`.
+    pub(crate) fn handle(&self, database: Database) -> DatabaseHandle {
+        DatabaseHandle {
+            database: Arc::new(Mutex::new(database)),
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Writes the readings still waiting & stops the thread.
+    pub(crate) fn shutdown(&mut self) -> Result<(), GenericError> {
+        // The thread only goes away once it's been stopped.
+        let _ = self.sender.send(Record::Stop);
+        match self.thread.take() {
+            Some(thread) => thread.join().map_err(|_| {
+                Box::new(ThreadJoinError {
+                    name: "database".to_string(),
+                }) as GenericError
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What the parts of the engine that record readings or load them share.
+#[derive(Clone)]
+pub(crate) struct DatabaseHandle {
+    database: Arc<Mutex<Database>>,
+    sender: Sender<Record>,
+}
+
+impl DatabaseHandle {
+    /// Records a reading taken now, in canonical units.
+    pub(crate) fn record(
+        &self,
+        id: &str,
+        label: &str,
+        kind: SensorKind,
+        value: f64,
+        units: &Units,
+    ) {
+        // The thread only stops as the engine shuts down.
+        let _ = self.sender.send(Record::Reading(StoredReading {
+            id: id.to_string(),
+            label: label.to_string(),
+            kind,
+            units: units.clone(),
+            time: SystemTime::now(),
+            value,
+        }));
+    }
+
+    /// Fills `history` with the minutes stored for `id` from the last `retention`, so that it
+    /// carries on from the last run.
+    pub(crate) fn preload(
+        &self,
+        id: &str,
+        history: &mut History,
+        retention: Duration,
+    ) -> Result<(), GenericError> {
+        let (now, system_now) = (Instant::now(), SystemTime::now());
+        let from = system_now.checked_sub(retention).unwrap_or(UNIX_EPOCH);
+        let samples = lock(&self.database)?.samples(id, from, system_now, Resolution::Minute)?;
+        for sample in samples {
+            let age = system_now.duration_since(sample.time).unwrap_or_default();
+            if let Some(time) = now.checked_sub(age) {
+                history.push(time, sample.mean);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Adds a reading to its minute or hour, keeping the minimum, maximum, sum & count.
+fn rollup_sql(resolution: Resolution) -> String {
+    format!(
+        "INSERT INTO {} (sensor, start, min, max, sum, count) VALUES (?1, ?2, ?3, ?3, ?3, 1) \
+         ON CONFLICT (sensor, start) DO UPDATE SET min = min(min, excluded.min), \
+         max = max(max, excluded.max), sum = sum + excluded.sum, count = count + 1",
+        resolution.table()
+    )
+}
+
+/// Milliseconds since the Unix epoch, as the database stores times.
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn system_time(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::Receiver;
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+    use crate::history::HistoryLimits;
+
+    const SECOND: Duration = Duration::from_secs(1);
+    const MINUTE: Duration = Duration::from_secs(60);
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    /// The directory goes away when it's dropped, so it's kept alongside the database.
+    fn database() -> (TempDir, Database) {
+        let directory = tempdir().unwrap();
+        let database = Database::open(&directory.path().join("readings.db")).unwrap();
+        (directory, database)
+    }
+
+    /// The start of an hour, so that readings a few minutes apart fall in the same hour.
+    fn hour() -> SystemTime {
+        system_time(HOUR_MILLIS * 1000)
+    }
+
+    fn reading(id: &str, label: &str, time: SystemTime, value: f64) -> StoredReading {
+        StoredReading {
+            id: id.to_string(),
+            label: label.to_string(),
+            kind: SensorKind::Temp,
+            units: Units::Celsius,
+            time,
+            value,
+        }
+    }
+
+    fn all(database: &Database, id: &str, resolution: Resolution) -> Vec<StoredSample> {
+        database
+            .samples(id, UNIX_EPOCH, system_time(i64::MAX), resolution)
+            .unwrap()
+    }
+
+    fn values(samples: &[StoredSample]) -> Vec<(f64, f64, f64, u64)> {
+        samples
+            .iter()
+            .map(|sample| (sample.min, sample.mean, sample.max, sample.count))
+            .collect()
+    }
+
+    fn handle(database: Database) -> (DatabaseHandle, Receiver<Record>) {
+        let (sender, receiver) = channel();
+        let handle = DatabaseHandle {
+            database: Arc::new(Mutex::new(database)),
+            sender,
+        };
+        (handle, receiver)
+    }
+
+    #[test]
+    fn stores_a_sensor_once_with_its_latest_label() {
+        let (_directory, mut database) = database();
+        let start = hour();
+        database
+            .insert(&[
+                reading("/cpu", "CPU", start, 40.0),
+                reading("/gpu", "GPU", start, 50.0),
+                reading("/cpu", "CPU", start + SECOND, 41.0),
+            ])
+            .unwrap();
+        database
+            .insert(&[reading("/cpu", "Package", start + SECOND * 2, 42.0)])
+            .unwrap();
+
+        let sensors = database.sensors().unwrap();
+        assert_eq!(
+            sensors
+                .iter()
+                .map(|sensor| (sensor.id.as_str(), sensor.label.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("/cpu", "Package"), ("/gpu", "GPU")]
+        );
+        assert_eq!(sensors[0].kind, SensorKind::Temp);
+        assert_eq!(sensors[0].units, Units::Celsius);
+        assert_eq!(
+            values(&all(&database, "/cpu", Resolution::Reading)),
+            vec![
+                (40.0, 40.0, 40.0, 1),
+                (41.0, 41.0, 41.0, 1),
+                (42.0, 42.0, 42.0, 1)
+            ]
+        );
+        assert_eq!(
+            values(&all(&database, "/gpu", Resolution::Reading)),
+            vec![(50.0, 50.0, 50.0, 1)]
+        );
+    }
+
+    #[test]
+    fn rolls_readings_up_into_minutes_and_hours() {
+        let (_directory, mut database) = database();
+        let start = hour();
+        database
+            .insert(&[
+                reading("/cpu", "CPU", start, 40.0),
+                reading("/cpu", "CPU", start + SECOND * 30, 50.0),
+                reading("/cpu", "CPU", start + SECOND * 59, 45.0),
+            ])
+            .unwrap();
+        database
+            .insert(&[
+                reading("/cpu", "CPU", start + MINUTE, 60.0),
+                reading("/cpu", "CPU", start + HOUR, 70.0),
+            ])
+            .unwrap();
+
+        let minutes = all(&database, "/cpu", Resolution::Minute);
+        assert_eq!(
+            minutes.iter().map(|sample| sample.time).collect::<Vec<_>>(),
+            vec![start, start + MINUTE, start + HOUR]
+        );
+        assert_eq!(
+            values(&minutes),
+            vec![
+                (40.0, 45.0, 50.0, 3),
+                (60.0, 60.0, 60.0, 1),
+                (70.0, 70.0, 70.0, 1),
+            ]
+        );
+        assert_eq!(
+            values(&all(&database, "/cpu", Resolution::Hour)),
+            vec![(40.0, 48.75, 60.0, 4), (70.0, 70.0, 70.0, 1)]
+        );
+    }
+
+    #[test]
+    fn forgets_each_resolution_after_its_retention() {
+        let (_directory, mut database) = database();
+        let now = hour() + HOUR * 24;
+        database
+            .insert(&[
+                reading("/cpu", "CPU", now - HOUR * 30, 30.0),
+                reading("/cpu", "CPU", now - HOUR * 20, 40.0),
+                reading("/cpu", "CPU", now - HOUR * 2, 50.0),
+                reading("/cpu", "CPU", now - MINUTE, 60.0),
+            ])
+            .unwrap();
+        let retention = Retention {
+            readings: HOUR,
+            minutes: HOUR * 10,
+            hours: HOUR * 24,
+        };
+
+        database.forget_old(&retention, now).unwrap();
+
+        assert_eq!(
+            values(&all(&database, "/cpu", Resolution::Reading)),
+            vec![(60.0, 60.0, 60.0, 1)]
+        );
+        assert_eq!(
+            values(&all(&database, "/cpu", Resolution::Minute)),
+            vec![(50.0, 50.0, 50.0, 1), (60.0, 60.0, 60.0, 1)]
+        );
+        assert_eq!(
+            values(&all(&database, "/cpu", Resolution::Hour)),
+            vec![
+                (40.0, 40.0, 40.0, 1),
+                (50.0, 50.0, 50.0, 1),
+                (60.0, 60.0, 60.0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn preloads_the_recent_minutes_into_a_history() {
+        let (_directory, mut database) = database();
+        // Minutes a few minutes ago, with an older one that's past the preloaded retention.
+        let now = millis(SystemTime::now());
+        let start = system_time(now - now % MINUTE_MILLIS) - MINUTE * 3;
+        database
+            .insert(&[
+                reading("/cpu", "CPU", start - HOUR * 2, 99.0),
+                reading("/cpu", "CPU", start + SECOND, 10.0),
+                reading("/cpu", "CPU", start + SECOND * 2, 20.0),
+                reading("/cpu", "CPU", start + MINUTE, 30.0),
+                reading("/gpu", "GPU", start, 50.0),
+            ])
+            .unwrap();
+        let (handle, _receiver) = handle(database);
+        let mut history = History::new(&HistoryLimits::default());
+
+        handle.preload("/cpu", &mut history, HOUR).unwrap();
+
+        let now = Instant::now();
+        let samples = history.range(now - HOUR, now);
+        assert_eq!(
+            samples
+                .iter()
+                .map(|sample| sample.value)
+                .collect::<Vec<_>>(),
+            vec![15.0, 30.0]
+        );
+        let age = now.duration_since(samples[0].time);
+        assert!(age >= MINUTE * 3 && age < MINUTE * 4, "{age:?}");
+    }
+}
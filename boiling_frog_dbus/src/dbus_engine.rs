@@ -1,28 +1,687 @@
+use std::collections::HashMap;
 use std::error::Error;
+use std::mem::take;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use log::error;
+
+use crate::aggregation::Aggregation;
+use crate::alerts::{AlertCallback, AlertSettings, AlertSource, Alerts};
+use crate::backend::{SensorBackend, SensorSink};
+use crate::baseline::{Baseline, BaselineRecorder, BaselineSampler};
+use crate::config::DEFAULT_STALE_AFTER;
+#[cfg(feature = "sqlite")]
+use crate::database::{aggregate_id, Database, DatabaseHandle, DatabaseRecorder, DatabaseSettings};
+#[cfg(feature = "dbus")]
 use crate::dbus_session::DbusSession;
+#[cfg(feature = "dbus")]
+use crate::desktop_notifications::{AlertDescriber, DesktopNotifications};
+use crate::error::NoBackendError;
+use crate::filter::SensorFilter;
+use crate::history::{History, HistoryLimits, Sample};
+#[cfg(feature = "hwmon")]
+use crate::hwmon_session::HwmonSession;
 use crate::mutex_helpers::lock;
-use crate::simple_types::{Fan, Temp};
+use crate::sensor_registry::SensorRegistry;
+use crate::simple_types::{
+    Reading, ReadingStatus, SensorCallback, SensorKind, SensorSnapshot, SubscriptionId,
+};
+use crate::sorted_property_observer::{builder, SortedPropertyObserver};
+use crate::thresholds::Thresholds;
+use crate::trend::{Trend, TrendSettings};
+use crate::units::Units;
+use crate::GenericError;
+
+/// The latest aggregate reading of each kind.
+type Cache = HashMap<SensorKind, Reading>;
+/// The aggregate readings of each kind over time.
+type Histories = HashMap<SensorKind, History>;
 
-#[derive(Debug)]
+/// Aggregates the readings from a list of [`SensorBackend`]s into one reading per
+/// [`SensorKind`], the highest of each unless another [`Aggregation`] is chosen.
 pub struct DbusEngine {
-    session: Arc<Mutex<DbusSession>>,
+    aggregations: HashMap<SensorKind, Aggregation>,
+    alerts: Arc<Mutex<Alerts>>,
+    backends: Vec<Box<dyn SensorBackend>>,
+    baseline: Option<BaselineSampler>,
+    cache: Arc<Mutex<Cache>>,
+    #[cfg(feature = "sqlite")]
+    database: Option<DatabaseRecorder>,
+    histories: Arc<Mutex<Histories>>,
+    #[cfg(feature = "dbus")]
+    notifications: Option<DesktopNotifications>,
+    observers: HashMap<SensorKind, SortedPropertyObserver>,
+    registry: Arc<Mutex<SensorRegistry>>,
+    stale_after: Duration,
+}
+
+pub struct DbusEngineBuilder {
+    aggregations: HashMap<SensorKind, Aggregation>,
+    alert_settings: AlertSettings,
+    backends: Vec<Box<dyn SensorBackend>>,
+    baseline_path: Option<PathBuf>,
+    #[cfg(feature = "sqlite")]
+    database: Option<DatabaseSettings>,
+    filter: SensorFilter,
+    history_limits: HistoryLimits,
+    stale_after: Duration,
+    thresholds: HashMap<SensorKind, Thresholds>,
+    trends: HashMap<SensorKind, TrendSettings>,
+}
+
+impl DbusEngineBuilder {
+    pub fn and(&mut self) -> &mut DbusEngineBuilder {
+        self
+    }
+
+    pub fn build(&mut self) -> Result<DbusEngine, GenericError> {
+        let cache = Arc::new(Mutex::new(
+            SensorKind::ALL
+                .iter()
+                .map(|kind| (*kind, Reading::never_received(*kind)))
+                .collect(),
+        ));
+        let histories = Arc::new(Mutex::new(
+            SensorKind::ALL
+                .iter()
+                .map(|kind| (*kind, History::new(&self.history_limits)))
+                .collect::<Histories>(),
+        ));
+        #[cfg(feature = "sqlite")]
+        let (database, database_handle) = match &self.database {
+            Some(settings) => {
+                let recorder = DatabaseRecorder::start(settings)?;
+                let handle = recorder.handle(Database::open(&settings.path)?);
+                let retention = self.history_limits.retention;
+                for (kind, history) in lock(&histories)?.iter_mut() {
+                    if let Err(e) = handle.preload(&aggregate_id(*kind), history, retention) {
+                        error!("Could not load the stored {} readings: {e}", kind.name());
+                    }
+                }
+                (Some(recorder), Some(handle))
+            }
+            None => (None, None),
+        };
+        let alerts = Arc::new(Mutex::new(Alerts::new(
+            &self.alert_settings,
+            &self.thresholds,
+            &self.trends,
+        )));
+        let observers = observers(
+            &alerts,
+            &cache,
+            #[cfg(feature = "sqlite")]
+            database_handle.as_ref(),
+            &histories,
+            &self.aggregations,
+        )?;
+        let registry = Arc::new(Mutex::new(SensorRegistry::new(
+            &self.filter,
+            &self.history_limits,
+        )));
+        let sink = SensorSink::new(
+            &alerts,
+            &registry,
+            &observers,
+            #[cfg(feature = "sqlite")]
+            database_handle.as_ref(),
+        );
+
+        // Any one backend is enough to get going. The fallbacks start last, so that they know
+        // whether the others' services are available.
+        let mut backends = Vec::new();
+        let mut reasons = Vec::new();
+        let mut ordered = take(&mut self.backends);
+        ordered.sort_by_key(|backend| backend.is_fallback());
+        for mut backend in ordered {
+            let started = backend.discover().and_then(|discovered| {
+                if !backend.is_fallback() {
+                    for sensor in discovered {
+                        sink.add(sensor)?;
+                    }
+                }
+                backend.start(sink.clone())
+            });
+            match started {
+                Ok(()) => backends.push(backend),
+                Err(e) => {
+                    error!("{} unavailable: {e}", backend.name());
+                    reasons.push(format!("{}: {e}", backend.name()));
+                }
+            }
+        }
+
+        if backends.is_empty() {
+            return Err(Box::new(NoBackendError {
+                reasons: reasons.join("; "),
+            }));
+        }
+
+        // A baseline that can't be read isn't recorded, rather than being overwritten.
+        let baseline = match self.baseline_path.as_deref() {
+            Some(path) => match BaselineRecorder::load(path) {
+                Ok(recorder) => Some(BaselineSampler::start(recorder, &registry)?),
+                Err(e) => {
+                    error!("Not recording the baseline in {}: {e}", path.display());
+                    None
+                }
+            },
+            None => None,
+        };
+
+        Ok(DbusEngine {
+            aggregations: self.aggregations.clone(),
+            alerts,
+            backends,
+            baseline,
+            cache,
+            #[cfg(feature = "sqlite")]
+            database,
+            histories,
+            #[cfg(feature = "dbus")]
+            notifications: None,
+            observers,
+            registry,
+            stale_after: self.stale_after,
+        })
+    }
+
+    pub fn with_aggregation(
+        &mut self,
+        kind: SensorKind,
+        aggregation: Aggregation,
+    ) -> &mut DbusEngineBuilder {
+        self.aggregations.insert(kind, aggregation);
+        self
+    }
+
+    /// When alerts are raised about readings crossing their thresholds.
+    pub fn with_alert_settings(&mut self, settings: &AlertSettings) -> &mut DbusEngineBuilder {
+        self.alert_settings = *settings;
+        self
+    }
+
+    /// Keeps a [`Baseline`] of the readings in the file at `path`, carrying on from what's
+    /// already there.
+    pub fn with_baseline_file(&mut self, path: &Path) -> &mut DbusEngineBuilder {
+        self.baseline_path = Some(path.to_path_buf());
+        self
+    }
+
+    /// Records every reading in an SQLite database, & carries on the histories from the
+    /// readings stored there.
+    #[cfg(feature = "sqlite")]
+    pub fn with_database(&mut self, settings: &DatabaseSettings) -> &mut DbusEngineBuilder {
+        self.database = Some(settings.clone());
+        self
+    }
+
+    pub fn with_backend(&mut self, backend: Box<dyn SensorBackend>) -> &mut DbusEngineBuilder {
+        self.backends.push(backend);
+        self
+    }
+
+    /// Chooses which of the sensors the backends discover are read.
+    pub fn with_filter(&mut self, filter: &SensorFilter) -> &mut DbusEngineBuilder {
+        self.filter = filter.clone();
+        self
+    }
+
+    /// How much history is kept for each sensor & aggregate.
+    pub fn with_history_limits(
+        &mut self,
+        history_limits: &HistoryLimits,
+    ) -> &mut DbusEngineBuilder {
+        self.history_limits = *history_limits;
+        self
+    }
+
+    /// How long a reading can go without an update before it's [`ReadingStatus::Stale`].
+    pub fn with_stale_after(&mut self, stale_after: Duration) -> &mut DbusEngineBuilder {
+        self.stale_after = stale_after;
+        self
+    }
+
+    /// The thresholds that readings of each kind raise alerts at. Kinds without thresholds never
+    /// raise alerts.
+    pub fn with_thresholds(
+        &mut self,
+        thresholds: &HashMap<SensorKind, Thresholds>,
+    ) -> &mut DbusEngineBuilder {
+        self.thresholds = thresholds.clone();
+        self
+    }
+
+    /// How fast readings of each kind have to be rising to raise alerts. Kinds without
+    /// [`TrendSettings`] have no [`DbusEngine::trend`].
+    pub fn with_trends(
+        &mut self,
+        trends: &HashMap<SensorKind, TrendSettings>,
+    ) -> &mut DbusEngineBuilder {
+        self.trends = trends.clone();
+        self
+    }
+
+    /// Adds Indicator Sensors on D-Bus & the kernel's hwmon sysfs interface as its fallback, as
+    /// far as the enabled features allow.
+    pub fn with_default_backends(&mut self) -> &mut DbusEngineBuilder {
+        #[cfg(feature = "dbus")]
+        self.with_backend(Box::new(DbusSession::new()));
+        #[cfg(feature = "hwmon")]
+        self.with_backend(Box::new(HwmonSession::new()));
+        self
+    }
 }
 
 impl DbusEngine {
     pub fn new() -> Result<DbusEngine, Box<dyn Error + Send + Sync>> {
-        let session = Arc::new(Mutex::new(DbusSession::new()));
-        DbusSession::run(&session)?;
+        DbusEngine::builder().with_default_backends().and().build()
+    }
+
+    pub fn builder() -> DbusEngineBuilder {
+        DbusEngineBuilder {
+            aggregations: HashMap::new(),
+            alert_settings: AlertSettings::default(),
+            backends: vec![],
+            baseline_path: None,
+            #[cfg(feature = "sqlite")]
+            database: None,
+            filter: SensorFilter::default(),
+            history_limits: HistoryLimits::default(),
+            stale_after: DEFAULT_STALE_AFTER,
+            thresholds: HashMap::new(),
+            trends: HashMap::new(),
+        }
+    }
+
+    /// How readings from `kind` sensors are reduced to the value [`DbusEngine::reading`] returns.
+    pub fn aggregation(&self, kind: SensorKind) -> Aggregation {
+        self.aggregations.get(&kind).cloned().unwrap_or_default()
+    }
+
+    /// The aggregate of the readings from every `kind` sensor.
+    pub fn reading(&self, kind: SensorKind) -> Result<Reading, Box<dyn Error + Send + Sync>> {
+        let mut reading = lock(&self.cache)?
+            .get(&kind)
+            .cloned()
+            .unwrap_or_else(|| Reading::never_received(kind));
+        reading.status = self.status(reading.updated);
+        Ok(reading)
+    }
+
+    /// The aggregate `kind` readings from `from` to `to`, oldest first & in canonical units,
+    /// preceded by the one that was current at `from`. There are gaps while there are no `kind`
+    /// sensors.
+    pub fn history(
+        &self,
+        kind: SensorKind,
+        from: Instant,
+        to: Instant,
+    ) -> Result<Vec<Sample>, Box<dyn Error + Send + Sync>> {
+        Ok(lock(&self.histories)?
+            .get(&kind)
+            .map(|history| history.range(from, to))
+            .unwrap_or_default())
+    }
+
+    /// The readings from the sensor with `id` from `from` to `to`, like [`DbusEngine::history`],
+    /// or `None` if there's no such sensor or it hasn't been read.
+    pub fn sensor_history(
+        &self,
+        id: &str,
+        from: Instant,
+        to: Instant,
+    ) -> Result<Option<Vec<Sample>>, Box<dyn Error + Send + Sync>> {
+        Ok(lock(&self.registry)?.history(id, from, to))
+    }
 
-        Ok(DbusEngine { session })
+    /// How the aggregate `kind` reading has been changing over its kind's
+    /// [`TrendSettings::period`], or `None` if it has no trend settings or hasn't been read for
+    /// that long.
+    pub fn trend(&self, kind: SensorKind) -> Result<Option<Trend>, Box<dyn Error + Send + Sync>> {
+        let now = Instant::now();
+        let alerts = lock(&self.alerts)?;
+        let Some(period) = alerts.trend_period(kind) else {
+            return Ok(None);
+        };
+        let samples = self.history(kind, now.checked_sub(period).unwrap_or(now), now)?;
+        Ok(alerts.trend(kind, &samples, now))
     }
 
-    pub fn fan(&self) -> Result<Fan, Box<dyn Error + Send + Sync>> {
-        Ok(lock(&self.session)?.fan())
+    /// How the reading from the sensor with `id` has been changing, like [`DbusEngine::trend`].
+    pub fn sensor_trend(&self, id: &str) -> Result<Option<Trend>, Box<dyn Error + Send + Sync>> {
+        let Some(snapshot) = self.sensor(id)? else {
+            return Ok(None);
+        };
+        let now = Instant::now();
+        let Some(period) = lock(&self.alerts)?.trend_period(snapshot.kind) else {
+            return Ok(None);
+        };
+        let samples = self
+            .sensor_history(id, now.checked_sub(period).unwrap_or(now), now)?
+            .unwrap_or_default();
+        Ok(lock(&self.alerts)?.trend(snapshot.kind, &samples, now))
     }
 
-    pub fn temp(&self) -> Result<Temp, Box<dyn Error + Send + Sync>> {
-        Ok(lock(&self.session)?.temp())
+    /// The baseline recorded so far, if there's a [`DbusEngineBuilder::with_baseline_file`].
+    pub fn baseline(&self) -> Result<Option<Baseline>, Box<dyn Error + Send + Sync>> {
+        match &self.baseline {
+            Some(sampler) => Ok(Some(sampler.baseline()?)),
+            None => Ok(None),
+        }
     }
+
+    /// Saves the readings added to the baseline since it was last saved, which otherwise happens
+    /// every few minutes & when the engine is dropped.
+    pub fn save_baseline(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(sampler) = &self.baseline {
+            sampler.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn fan(&self) -> Result<Reading, Box<dyn Error + Send + Sync>> {
+        self.reading(SensorKind::Fan)
+    }
+
+    pub fn temp(&self) -> Result<Reading, Box<dyn Error + Send + Sync>> {
+        self.reading(SensorKind::Temp)
+    }
+
+    /// True while no backend has any sensors, e.g. until Indicator Sensors starts.
+    pub fn is_waiting(&self) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        Ok(lock(&self.registry)?.is_empty())
+    }
+
+    /// Every sensor the backends currently know about, ordered by label.
+    pub fn sensors(&self) -> Result<Vec<SensorSnapshot>, Box<dyn Error + Send + Sync>> {
+        let mut snapshots = lock(&self.registry)?.snapshots();
+        for snapshot in &mut snapshots {
+            snapshot.status = self.status(snapshot.updated);
+        }
+        Ok(snapshots)
+    }
+
+    pub fn sensor(&self, id: &str) -> Result<Option<SensorSnapshot>, Box<dyn Error + Send + Sync>> {
+        let mut snapshot = lock(&self.registry)?.get(id).cloned();
+        if let Some(snapshot) = &mut snapshot {
+            snapshot.status = self.status(snapshot.updated);
+        }
+        Ok(snapshot)
+    }
+
+    /// Calls `callback` with each new reading from the sensor with `id`. The sensor needn't have
+    /// been discovered yet.
+    pub fn subscribe(
+        &self,
+        id: &str,
+        callback: &SensorCallback,
+    ) -> Result<SubscriptionId, Box<dyn Error + Send + Sync>> {
+        Ok(lock(&self.registry)?.subscribe(id, callback))
+    }
+
+    /// Calls `callback` with every reading from every sensor that isn't filtered out, including
+    /// sensors added later, from whichever thread the reading arrived on.
+    pub fn subscribe_all(
+        &self,
+        callback: &SensorCallback,
+    ) -> Result<SubscriptionId, Box<dyn Error + Send + Sync>> {
+        Ok(lock(&self.registry)?.subscribe_all(callback))
+    }
+
+    /// Ends a subscription from [`DbusEngine::subscribe`] or [`DbusEngine::subscribe_all`].
+    pub fn unsubscribe(
+        &self,
+        subscription_id: SubscriptionId,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        lock(&self.registry)?.unsubscribe(subscription_id);
+        Ok(())
+    }
+
+    /// Calls `callback` with each alert, from whichever thread the reading that raised it
+    /// arrived on.
+    pub fn subscribe_alerts(
+        &self,
+        callback: &AlertCallback,
+    ) -> Result<SubscriptionId, Box<dyn Error + Send + Sync>> {
+        Ok(lock(&self.alerts)?.subscribe(callback))
+    }
+
+    pub fn unsubscribe_alerts(
+        &self,
+        subscription_id: SubscriptionId,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        lock(&self.alerts)?.unsubscribe(subscription_id);
+        Ok(())
+    }
+
+    /// Stops raising alerts for `duration`, e.g. [`crate::alerts::SNOOZE`].
+    pub fn snooze(&self, duration: Duration) -> Result<(), Box<dyn Error + Send + Sync>> {
+        lock(&self.alerts)?.snooze(duration);
+        Ok(())
+    }
+
+    /// Stops raising alerts about the reading from `source` for `duration`, e.g. when its
+    /// notification's "Snooze" button is pressed.
+    pub fn snooze_source(
+        &self,
+        source: &AlertSource,
+        duration: Duration,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        lock(&self.alerts)?.snooze_source(source, duration);
+        Ok(())
+    }
+
+    /// Shows each alert as a desktop notification through the freedesktop.org notification
+    /// server, with a "Snooze" action, for when there's no GApplication to send them. `describe`
+    /// gives each alert's summary & body.
+    #[cfg(feature = "dbus")]
+    pub fn notify_desktop(
+        &mut self,
+        app_name: &str,
+        describe: AlertDescriber,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(mut notifications) = self.notifications.take() {
+            notifications.shutdown()?;
+        }
+        self.notifications = Some(DesktopNotifications::start(
+            &self.alerts,
+            app_name,
+            describe,
+        )?);
+        Ok(())
+    }
+
+    /// Changes which sensors are aggregated. Sensors that were filtered out come back with their
+    /// latest reading.
+    pub fn set_filter(
+        &mut self,
+        filter: &SensorFilter,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // The registry stays locked so that no reading can slip in between the two updates.
+        let mut registry = lock(&self.registry)?;
+        let (included, excluded) = registry.set_filter(filter);
+        for snapshot in excluded {
+            if let Some(observer) = self.observers.get(&snapshot.kind) {
+                observer.remove(&snapshot.id)?;
+            }
+        }
+        for snapshot in included {
+            if let (Some(observer), Some(value)) =
+                (self.observers.get(&snapshot.kind), snapshot.value)
+            {
+                observer.update(
+                    &snapshot.id,
+                    &snapshot.label,
+                    value,
+                    &snapshot.units,
+                    snapshot.updated,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn set_aggregation(
+        &mut self,
+        kind: SensorKind,
+        aggregation: Aggregation,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(observer) = self.observers.get(&kind) {
+            observer.set_aggregation(&aggregation)?;
+        }
+        self.aggregations.insert(kind, aggregation);
+
+        Ok(())
+    }
+
+    pub fn set_stale_after(&mut self, stale_after: Duration) {
+        self.stale_after = stale_after;
+    }
+
+    pub fn set_alert_settings(
+        &mut self,
+        settings: &AlertSettings,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        lock(&self.alerts)?.set_settings(settings);
+        Ok(())
+    }
+
+    pub fn set_thresholds(
+        &mut self,
+        thresholds: &HashMap<SensorKind, Thresholds>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        lock(&self.alerts)?.set_thresholds(thresholds);
+        Ok(())
+    }
+
+    pub fn set_trends(
+        &mut self,
+        trends: &HashMap<SensorKind, TrendSettings>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        lock(&self.alerts)?.set_trends(trends);
+        Ok(())
+    }
+
+    fn status(&self, updated: Option<Instant>) -> ReadingStatus {
+        ReadingStatus::of(updated, self.stale_after)
+    }
+}
+
+impl Drop for DbusEngine {
+    fn drop(&mut self) {
+        #[cfg(feature = "dbus")]
+        if let Some(mut notifications) = self.notifications.take() {
+            if let Err(e) = notifications.shutdown() {
+                error!("Desktop notifications did not shut down: {e}");
+            }
+        }
+        for backend in &mut self.backends {
+            if let Err(e) = backend.shutdown() {
+                error!("{} did not shut down: {e}", backend.name());
+            }
+        }
+        if let Some(mut sampler) = self.baseline.take() {
+            if let Err(e) = sampler.shutdown() {
+                error!("The baseline did not shut down: {e}");
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(mut database) = self.database.take() {
+            if let Err(e) = database.shutdown() {
+                error!("The database did not shut down: {e}");
+            }
+        }
+    }
+}
+
+/// Builds an observer for each kind of sensor that keeps its cached reading up to date.
+fn observers(
+    alerts_ref: &Arc<Mutex<Alerts>>,
+    cache_ref: &Arc<Mutex<Cache>>,
+    #[cfg(feature = "sqlite")] database_ref: Option<&DatabaseHandle>,
+    histories_ref: &Arc<Mutex<Histories>>,
+    aggregations: &HashMap<SensorKind, Aggregation>,
+) -> Result<HashMap<SensorKind, SortedPropertyObserver>, GenericError> {
+    let mut observers = HashMap::new();
+    for kind in SensorKind::ALL {
+        let callback_alerts = alerts_ref.clone();
+        let callback_cache = cache_ref.clone();
+        #[cfg(feature = "sqlite")]
+        let callback_database = database_ref.cloned();
+        let callback_histories = histories_ref.clone();
+        let observer = builder()
+            .with_aggregation(&aggregations.get(&kind).cloned().unwrap_or_default())
+            .and()
+            .with_on_change_callback(&Arc::new(Mutex::new(Box::new(
+                move |label: String, value: f64, units: Units, updated: Option<Instant>| {
+                    let source = AlertSource::Aggregate(kind);
+                    let (reading, (alerts, callbacks)) = match updated {
+                        // The group is empty.
+                        None => {
+                            lock(&callback_alerts)?.forget(&source);
+                            (Reading::never_received(kind), (vec![], vec![]))
+                        }
+                        Some(time) => {
+                            #[cfg(feature = "sqlite")]
+                            if let Some(database) = &callback_database {
+                                database.record(
+                                    &aggregate_id(kind),
+                                    kind.name(),
+                                    kind,
+                                    value,
+                                    &units,
+                                );
+                            }
+                            let period = lock(&callback_alerts)?.trend_period(kind);
+                            let mut samples = vec![];
+                            if let Some(history) = lock(&callback_histories)?.get_mut(&kind) {
+                                history.push(time, value);
+                                if let Some(period) = period {
+                                    let from = time.checked_sub(period).unwrap_or(time);
+                                    samples = history.range(from, time);
+                                }
+                            }
+                            // Means & medians don't come from any one sensor, so they have no
+                            // label of their own.
+                            let alert_label = match label.as_str() {
+                                "" => kind.quantity(),
+                                label => label,
+                            };
+                            let checked = lock(&callback_alerts)?.check(
+                                source,
+                                alert_label,
+                                kind,
+                                value,
+                                &units,
+                                &samples,
+                            );
+                            let reading = Reading {
+                                label,
+                                value,
+                                units,
+                                updated,
+                                status: ReadingStatus::Fresh,
+                            };
+                            (reading, checked)
+                        }
+                    };
+                    lock(&callback_cache)?.insert(kind, reading);
+                    for alert in alerts {
+                        for callback in &callbacks {
+                            lock(callback)?(alert.clone())?;
+                        }
+                    }
+                    Ok(())
+                },
+            ))))
+            .and()
+            .build()?;
+        observers.insert(kind, observer);
+    }
+
+    Ok(observers)
 }
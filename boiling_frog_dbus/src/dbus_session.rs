@@ -1,94 +1,142 @@
 use std::collections::HashMap;
-use std::error::Error;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::thread::{spawn, JoinHandle};
 
-use log::error;
-use zbus::blocking::fdo::ObjectManagerProxy;
+use async_io::Timer;
+use futures_util::future::{abortable, AbortHandle};
+use futures_util::stream::select;
+use futures_util::StreamExt;
+use log::{debug, error, info};
+use zbus::blocking::fdo::{DBusProxy, ObjectManagerProxy};
 use zbus::blocking::Connection;
-use zbus::names::OwnedInterfaceName;
+use zbus::fdo::{InterfacesAdded, InterfacesRemoved, NameOwnerChanged, PropertiesChanged};
+use zbus::names::{BusName, OwnedInterfaceName};
 use zbus::zvariant::{OwnedObjectPath, OwnedValue, Str};
+use zbus::{block_on, MatchRule, Message, MessageStream, MessageType};
 
-use crate::config::{ACTIVE_SENSORS_PATH, INDICATOR_SENSORS_SERVICE};
+use crate::backend::{Sensor, SensorBackend, SensorSink};
+use crate::config::{ACTIVE_SENSORS_PATH, DBUS_REFRESH_RATE, INDICATOR_SENSORS_SERVICE};
 use crate::dbus_info::DbusInfo;
+use crate::error::{BadPropertyTypeError, NotDiscoveredError, ThreadJoinError};
 use crate::metric::Metric;
-use crate::mutex_helpers::lock;
-use crate::simple_types::{Fan, Temp};
-use crate::sorted_property_observer::builder;
+use crate::metric_value::MetricValue;
+use crate::GenericError;
 
+/// Reads sensors from [Indicator Sensors](https://github.com/alexmurray/indicator-sensors) over
+/// the D-Bus session bus.
+///
+/// All sensors share one connection, and their changes are received on one executor thread.
 #[derive(Debug)]
-pub(crate) struct DbusSession {
-    cached_fan: Fan,
-    cached_temp: Temp,
+pub struct DbusSession {
+    address: ServiceAddress,
+    connection: Option<Connection>,
+    has_owner: bool,
+    metrics: Vec<Metric>,
+    listener: Option<(AbortHandle, JoinHandle<()>)>,
+}
+
+/// Where the sensors are published on the bus.
+#[derive(Clone, Debug)]
+struct ServiceAddress {
+    service: String,
+    /// The object manager's path, under which each sensor has an object.
+    path: String,
+}
+
+impl Default for DbusSession {
+    fn default() -> Self {
+        DbusSession::with_service(INDICATOR_SENSORS_SERVICE, ACTIVE_SENSORS_PATH)
+    }
 }
 
 impl DbusSession {
-    pub(crate) fn new() -> DbusSession {
+    pub fn new() -> DbusSession {
+        DbusSession::default()
+    }
+
+    /// Reads sensors from the object manager at `path` on `service` rather than Indicator
+    /// Sensors' usual one.
+    pub fn with_service(service: &str, path: &str) -> DbusSession {
         DbusSession {
-            cached_fan: Fan {
-                label: "".to_string(),
-                value: 0 as f64,
-                units: "".to_string(),
-            },
-            cached_temp: Temp {
-                label: "".to_string(),
-                value: 0 as f64,
-                units: "".to_string(),
+            address: ServiceAddress {
+                service: service.to_string(),
+                path: path.to_string(),
             },
+            connection: None,
+            has_owner: false,
+            metrics: vec![],
+            listener: None,
         }
     }
+}
 
-    pub(crate) fn fan(&self) -> Fan {
-        self.cached_fan.clone()
-    }
-
-    pub(crate) fn temp(&self) -> Temp {
-        self.cached_temp.clone()
+impl SensorBackend for DbusSession {
+    fn name(&self) -> &str {
+        "Indicator Sensors"
     }
 
-    pub(crate) fn run(
-        session_ref: &Arc<Mutex<DbusSession>>,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    fn discover(&mut self) -> Result<Vec<Sensor>, GenericError> {
         let connection = Connection::session().map_err(|e| {
             error!("zbus signal: {e}");
             e
         })?;
-        let object_manager_proxy: ObjectManagerProxy = ObjectManagerProxy::builder(&connection)
-            .destination(INDICATOR_SENSORS_SERVICE)?
-            .path(ACTIVE_SENSORS_PATH)?
-            .build()?;
-        let managed_objects = object_manager_proxy.get_managed_objects()?;
-        log_out(&managed_objects);
-        let (fan_objects, temp_objects) = parse_objects(&managed_objects);
-
-        let fan_callback_session = session_ref.clone();
-        builder()
-            .with_metrics(&fan_objects)
-            .and()
-            .with_on_change_callback(&Arc::new(Mutex::new(Box::new(
-                move |label: String, value: f64, units: String| {
-                    let mut locked = lock(&fan_callback_session)?;
-                    locked.cached_fan.label = label;
-                    locked.cached_fan.units = units;
-                    locked.cached_fan.value = value;
-                    Ok(())
-                },
-            ))))
-            .build()?;
-
-        let temp_callback_session = session_ref.clone();
-        builder()
-            .with_metrics(&temp_objects)
-            .and()
-            .with_on_change_callback(&Arc::new(Mutex::new(Box::new(
-                move |label: String, value: f64, units: String| {
-                    let mut locked = lock(&temp_callback_session)?;
-                    locked.cached_temp.label = label;
-                    locked.cached_temp.units = units;
-                    locked.cached_temp.value = value;
-                    Ok(())
-                },
-            ))))
-            .build()?;
+        // Without the service there's nothing to discover yet, but the listener will pick it up
+        // once it starts.
+        self.has_owner = DBusProxy::new(&connection)?
+            .name_has_owner(BusName::try_from(self.address.service.as_str())?)?;
+        self.metrics = if self.has_owner {
+            let object_manager_proxy: ObjectManagerProxy = ObjectManagerProxy::builder(&connection)
+                .destination(self.address.service.as_str())?
+                .path(self.address.path.as_str())?
+                .build()?;
+            let managed_objects = object_manager_proxy.get_managed_objects()?;
+            log_out(&managed_objects);
+            parse_objects(&managed_objects)
+        } else {
+            debug!("waiting for {}", self.address.service);
+            vec![]
+        };
+        self.connection = Some(connection);
+
+        Ok(self.metrics.iter().map(Metric::sensor).collect())
+    }
+
+    fn start(&mut self, sink: SensorSink) -> Result<(), GenericError> {
+        let connection = self
+            .connection
+            .as_ref()
+            .ok_or(NotDiscoveredError {
+                name: self.name().to_string(),
+            })?
+            .inner()
+            .clone();
+        let address = self.address.clone();
+        let metrics = self.metrics.clone();
+        sink.set_primary_available(self.has_owner);
+        let (listener, abort_handle) = abortable(async move {
+            if let Err(e) = listen(&connection, &address, &metrics, &sink).await {
+                error!("Indicator Sensors listener stopped: {e}");
+            }
+            // Its sensors no longer update, so the fallbacks take over.
+            sink.set_primary_available(false);
+        });
+        let thread = spawn(move || {
+            // An abort is how shutdown stops the listener, so it's not an error.
+            let _ = block_on(listener);
+        });
+        self.listener = Some((abort_handle, thread));
+
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), GenericError> {
+        if let Some((abort_handle, thread)) = self.listener.take() {
+            abort_handle.abort();
+            thread.join().map_err(|_| ThreadJoinError {
+                name: self.name().to_string(),
+            })?;
+        }
+        self.connection = None;
 
         Ok(())
     }
@@ -96,25 +144,287 @@ impl DbusSession {
 
 fn parse_objects(
     objects: &HashMap<OwnedObjectPath, HashMap<OwnedInterfaceName, HashMap<String, OwnedValue>>>,
-) -> (Vec<Metric>, Vec<Metric>) {
-    let mut fans = Vec::new();
-    let mut temps = Vec::new();
+) -> Vec<Metric> {
+    let mut metrics = Vec::new();
     for (path, owned_object_path_map) in objects {
         for (interface_name, value_map) in owned_object_path_map {
             let dbus_info = DbusInfo::new(interface_name, path);
-            let metric_option = Metric::try_metric(&dbus_info, value_map);
-            if let Some(metric) = metric_option {
-                if !metric.get_value().dbus_info.path.contains("/virtual/") {
-                    match metric {
-                        Metric::Fan(_) => fans.push(metric.clone()),
-                        Metric::Temp(_) => temps.push(metric.clone()),
-                    };
+            if let Some(metric) = Metric::try_metric(&dbus_info, value_map) {
+                metrics.push(metric);
+            }
+        }
+    }
+
+    metrics
+}
+
+/// What the listener wakes up for.
+enum Event {
+    Signal(zbus::Result<Arc<Message>>),
+    Refresh,
+}
+
+async fn listen(
+    connection: &zbus::Connection,
+    address: &ServiceAddress,
+    metrics: &[Metric],
+    sink: &SensorSink,
+) -> Result<(), GenericError> {
+    // One rule covers property changes on every sensor under the active sensors path, however many
+    // there are, as well as the object manager's own InterfacesAdded & InterfacesRemoved signals.
+    let sensors_rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .path_namespace(address.path.as_str())?
+        .build();
+    let owner_rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .sender("org.freedesktop.DBus")?
+        .interface("org.freedesktop.DBus")?
+        .member("NameOwnerChanged")?
+        .arg(0, address.service.as_str())?
+        .build();
+    let signals = select(
+        MessageStream::for_match_rule(sensors_rule, connection, None).await?,
+        MessageStream::for_match_rule(owner_rule, connection, None).await?,
+    );
+    // A reading that holds steady is never signalled, so the latest values are recorded again now
+    // & then to keep them from going stale while the service is still running.
+    let mut events = select(
+        signals.map(Event::Signal),
+        Timer::interval(DBUS_REFRESH_RATE).map(|_| Event::Refresh),
+    );
+
+    // Sensors or the service itself may have come or gone between discovery & subscribing, so
+    // the sensors are looked up again now that no signal can be missed.
+    let current = managed_metrics(connection, address).await?;
+    sink.set_primary_available(current.is_some());
+    let current = current.unwrap_or_default();
+    for metric in metrics {
+        let path = &metric.get_value().dbus_info.path;
+        if !current
+            .iter()
+            .any(|current| current.get_value().dbus_info.path == *path)
+        {
+            debug!("{} went away before listening", path);
+            sink.remove(path)?;
+        }
+    }
+    let mut values = HashMap::new();
+    for metric in &current {
+        let path = &metric.get_value().dbus_info.path;
+        if !metrics
+            .iter()
+            .any(|discovered| discovered.get_value().dbus_info.path == *path)
+        {
+            debug!("{} appeared before listening", path);
+            sink.add(metric.sensor())?;
+        }
+        start_metric(metric, &mut values, sink)?;
+    }
+
+    while let Some(event) = events.next().await {
+        match event {
+            Event::Signal(message) => {
+                // A bad message is skipped, as ending the listener would freeze every reading.
+                let message = match message {
+                    Ok(message) => message,
+                    Err(e) => {
+                        error!("Could not receive a signal: {e}");
+                        continue;
+                    }
+                };
+                if let Err(e) = on_signal(connection, address, message, &mut values, sink).await {
+                    error!("Could not handle a signal: {e}");
+                }
+            }
+            Event::Refresh => {
+                if let Err(e) = refresh(&values, sink) {
+                    error!("Could not re-read the sensors: {e}");
                 }
             }
         }
     }
 
-    (fans, temps)
+    Ok(())
+}
+
+fn start_metric(
+    metric: &Metric,
+    values: &mut HashMap<String, MetricValue>,
+    sink: &SensorSink,
+) -> Result<(), GenericError> {
+    let value = metric.get_value();
+    debug!(
+        "listening for {} = {}{}",
+        value.label, value.value, value.units
+    );
+    sink.update(&value.dbus_info.path, value.value, &value.units)?;
+    values.insert(value.dbus_info.path.clone(), value.clone());
+
+    Ok(())
+}
+
+async fn on_signal(
+    connection: &zbus::Connection,
+    address: &ServiceAddress,
+    message: Arc<Message>,
+    values: &mut HashMap<String, MetricValue>,
+    sink: &SensorSink,
+) -> Result<(), GenericError> {
+    if let Some(signal) = NameOwnerChanged::from_message(message.clone()) {
+        on_name_owner_changed(connection, address, &signal, values, sink).await
+    } else if let Some(signal) = PropertiesChanged::from_message(message.clone()) {
+        on_properties_changed(&signal, values, sink)
+    } else if let Some(signal) = InterfacesAdded::from_message(message.clone()) {
+        on_interfaces_added(&signal, values, sink)
+    } else if let Some(signal) = InterfacesRemoved::from_message(message) {
+        on_interfaces_removed(&signal, values, sink)
+    } else {
+        Ok(())
+    }
+}
+
+/// Forgets every sensor when Indicator Sensors goes away, so that their readings don't freeze, and
+/// rediscovers them all when it comes back.
+async fn on_name_owner_changed(
+    connection: &zbus::Connection,
+    address: &ServiceAddress,
+    signal: &NameOwnerChanged,
+    values: &mut HashMap<String, MetricValue>,
+    sink: &SensorSink,
+) -> Result<(), GenericError> {
+    let args = signal.args()?;
+    if args.name().as_str() != address.service {
+        return Ok(());
+    }
+
+    for (path, _) in values.drain() {
+        sink.remove(&path)?;
+    }
+
+    sink.set_primary_available(args.new_owner().is_some());
+    if args.new_owner().is_none() {
+        info!("{} went away", address.service);
+        return Ok(());
+    }
+
+    info!("{} is back", address.service);
+    for metric in managed_metrics(connection, address)
+        .await?
+        .unwrap_or_default()
+    {
+        sink.add(metric.sensor())?;
+        start_metric(&metric, values, sink)?;
+    }
+
+    Ok(())
+}
+
+/// Records the latest value of every sensor being listened to again, so that the steady ones
+/// stay fresh while the service is running.
+fn refresh(values: &HashMap<String, MetricValue>, sink: &SensorSink) -> Result<(), GenericError> {
+    for (path, value) in values {
+        sink.update(path, value.value, &value.units)?;
+    }
+
+    Ok(())
+}
+
+/// The sensors the service currently has, or `None` if it isn't running.
+async fn managed_metrics(
+    connection: &zbus::Connection,
+    address: &ServiceAddress,
+) -> Result<Option<Vec<Metric>>, GenericError> {
+    let has_owner = zbus::fdo::DBusProxy::new(connection)
+        .await?
+        .name_has_owner(BusName::try_from(address.service.as_str())?)
+        .await?;
+    if !has_owner {
+        return Ok(None);
+    }
+
+    let object_manager_proxy = zbus::fdo::ObjectManagerProxy::builder(connection)
+        .destination(address.service.as_str())?
+        .path(address.path.as_str())?
+        .build()
+        .await?;
+    let managed_objects = object_manager_proxy.get_managed_objects().await?;
+    log_out(&managed_objects);
+    Ok(Some(parse_objects(&managed_objects)))
+}
+
+fn on_properties_changed(
+    signal: &PropertiesChanged,
+    values: &mut HashMap<String, MetricValue>,
+    sink: &SensorSink,
+) -> Result<(), GenericError> {
+    let Some(path) = signal.path().map(|path| path.to_string()) else {
+        return Ok(());
+    };
+    let Some(metric_value) = values.get_mut(&path) else {
+        return Ok(());
+    };
+    let args = signal.args()?;
+    if args.interface_name().as_str() != metric_value.dbus_info.interface_name {
+        return Ok(());
+    }
+    if let Some(value) = args.changed_properties().get("Value") {
+        let value = *value.downcast_ref::<f64>().ok_or(BadPropertyTypeError {})?;
+        debug!(
+            "{} changed to {}{}",
+            metric_value.label, value, metric_value.units
+        );
+        metric_value.value = value;
+        sink.update(&path, value, &metric_value.units)?;
+    }
+
+    Ok(())
+}
+
+fn on_interfaces_added(
+    signal: &InterfacesAdded,
+    values: &mut HashMap<String, MetricValue>,
+    sink: &SensorSink,
+) -> Result<(), GenericError> {
+    let args = signal.args()?;
+    for (interface_name, properties) in args.interfaces_and_properties() {
+        let dbus_info = DbusInfo {
+            interface_name: interface_name.to_string(),
+            path: args.object_path().to_string(),
+        };
+        let value_map = properties
+            .iter()
+            .map(|(name, value)| (name.to_string(), OwnedValue::from(value)))
+            .collect();
+        if let Some(metric) = Metric::try_metric(&dbus_info, &value_map) {
+            debug!("added metric = {}", metric);
+            sink.add(metric.sensor())?;
+            start_metric(&metric, values, sink)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn on_interfaces_removed(
+    signal: &InterfacesRemoved,
+    values: &mut HashMap<String, MetricValue>,
+    sink: &SensorSink,
+) -> Result<(), GenericError> {
+    let args = signal.args()?;
+    let path = args.object_path().to_string();
+    let removed = values.get(&path).is_some_and(|value| {
+        args.interfaces()
+            .iter()
+            .any(|interface_name| *interface_name == value.dbus_info.interface_name)
+    });
+    if removed {
+        debug!("removed metric at {}", path);
+        values.remove(&path);
+        sink.remove(&path)?;
+    }
+
+    Ok(())
 }
 
 fn log_out(
@@ -124,19 +434,19 @@ fn log_out(
         for (iname, map) in path_map.iter() {
             let dbus_info = DbusInfo::new(iname, path);
             if let Some(metric) = Metric::try_metric(&dbus_info, map) {
-                println!("metric = {}", metric);
+                debug!("metric = {}", metric);
             } else {
-                println!("could not handle {}", dbus_info);
+                debug!("could not handle {}", dbus_info);
 
                 for (value_name, owned_value) in map.iter() {
                     if let Some(value) = &owned_value.downcast_ref::<Str>() {
-                        println!(
+                        debug!(
                             "path = {}, interface name = {}, value_name = {}, \
                     owned_value = {}",
                             path, iname, value_name, value
                         );
                     } else {
-                        println!(
+                        debug!(
                             "path = {}, interface name = {}, value_name = {}",
                             path, iname, value_name
                         );
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, spawn, JoinHandle};
+
+use futures_util::future::{abortable, AbortHandle};
+use futures_util::StreamExt;
+use log::error;
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+use zbus::{block_on, MatchRule, MessageStream, MessageType};
+
+use crate::alerts::{Alert, AlertSource, Alerts, SNOOZE};
+use crate::config::{NOTIFICATIONS_PATH, NOTIFICATIONS_SERVICE};
+use crate::error::ThreadJoinError;
+use crate::mutex_helpers::lock;
+use crate::simple_types::SubscriptionId;
+use crate::thresholds::Level;
+use crate::GenericError;
+
+const SNOOZE_ACTION: &str = "snooze";
+
+/// Gives the summary & body of the notification for an alert.
+pub type AlertDescriber = Box<dyn Fn(&Alert) -> (String, String) + Send + Sync + 'static>;
+
+/// Each source's latest notification, which the next one about it replaces.
+type NotificationIds = HashMap<AlertSource, u32>;
+
+/// What the notifier thread is sent.
+enum Message {
+    Alert(Alert),
+    Stop,
+}
+
+/// Sends each alert to the desktop's notification server, & snoozes the alert's source when its
+/// notification's "Snooze" action is chosen.
+///
+/// Notifications are sent on their own thread, so that a slow notification server doesn't hold
+/// up the readings.
+pub(crate) struct DesktopNotifications {
+    alerts: Arc<Mutex<Alerts>>,
+    listener: Option<(AbortHandle, JoinHandle<()>)>,
+    notifier: Option<(Sender<Message>, JoinHandle<()>)>,
+    subscription: SubscriptionId,
+}
+
+impl DesktopNotifications {
+    pub(crate) fn start(
+        alerts: &Arc<Mutex<Alerts>>,
+        app_name: &str,
+        describe: AlertDescriber,
+    ) -> Result<DesktopNotifications, GenericError> {
+        let connection = Connection::session()?;
+        let ids = Arc::new(Mutex::new(NotificationIds::new()));
+
+        let listener_connection = connection.inner().clone();
+        let listener_alerts = alerts.clone();
+        let listener_ids = ids.clone();
+        let (listener, abort_handle) = abortable(async move {
+            if let Err(e) = listen(&listener_connection, &listener_alerts, &listener_ids).await {
+                error!("Notification action listener stopped: {e}");
+            }
+        });
+        let thread = spawn(move || {
+            // An abort is how shutdown stops the listener, so it's not an error.
+            let _ = block_on(listener);
+        });
+
+        let (sender, receiver) = channel();
+        let app_name = app_name.to_string();
+        let notifier = thread::Builder::new()
+            .name("desktop notifications".to_string())
+            .spawn(move || {
+                send_notifications(&receiver, &connection, &app_name, &describe, &ids)
+            })?;
+
+        let alert_sender = sender.clone();
+        let subscription =
+            lock(alerts)?.subscribe(&Arc::new(Mutex::new(Box::new(move |alert: Alert| {
+                // The thread only stops as the notifications shut down.
+                let _ = alert_sender.send(Message::Alert(alert));
+                Ok(())
+            }))));
+
+        Ok(DesktopNotifications {
+            alerts: alerts.clone(),
+            listener: Some((abort_handle, thread)),
+            notifier: Some((sender, notifier)),
+            subscription,
+        })
+    }
+
+    pub(crate) fn shutdown(&mut self) -> Result<(), GenericError> {
+        lock(&self.alerts)?.unsubscribe(self.subscription);
+        if let Some((sender, thread)) = self.notifier.take() {
+            let _ = sender.send(Message::Stop);
+            thread.join().map_err(|_| ThreadJoinError {
+                name: "desktop notifications".to_string(),
+            })?;
+        }
+        if let Some((abort_handle, thread)) = self.listener.take() {
+            abort_handle.abort();
+            thread.join().map_err(|_| ThreadJoinError {
+                name: "desktop notifications".to_string(),
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends a notification for each alert it receives until it's told to stop.
+fn send_notifications(
+    receiver: &Receiver<Message>,
+    connection: &Connection,
+    app_name: &str,
+    describe: &AlertDescriber,
+    ids: &Arc<Mutex<NotificationIds>>,
+) {
+    while let Ok(Message::Alert(alert)) = receiver.recv() {
+        // Not having a notification server shouldn't stop the alerts.
+        if let Err(e) = notify(connection, app_name, describe, ids, &alert) {
+            error!("Could not send a notification: {e}");
+        }
+    }
+}
+
+/// https://specifications.freedesktop.org/notification-spec/latest/protocol.html#command-notify
+fn notify(
+    connection: &Connection,
+    app_name: &str,
+    describe: &AlertDescriber,
+    ids: &Arc<Mutex<NotificationIds>>,
+    alert: &Alert,
+) -> Result<(), GenericError> {
+    let (summary, body) = describe(alert);
+    let replaces_id = lock(ids)?.get(&alert.source).copied().unwrap_or(0);
+    let urgency: u8 = if alert.level() == Level::Critical {
+        2
+    } else {
+        1
+    };
+    let hints = HashMap::from([("urgency", Value::from(urgency))]);
+    let reply = connection.call_method(
+        Some(NOTIFICATIONS_SERVICE),
+        NOTIFICATIONS_PATH,
+        Some(NOTIFICATIONS_SERVICE),
+        "Notify",
+        &(
+            app_name,
+            replaces_id,
+            "",
+            summary.as_str(),
+            body.as_str(),
+            vec![SNOOZE_ACTION, "Snooze 10 min"],
+            hints,
+            -1_i32,
+        ),
+    )?;
+    let id: u32 = reply.body()?;
+    lock(ids)?.insert(alert.source.clone(), id);
+
+    Ok(())
+}
+
+/// Snoozes the alerts about a reading whenever the "Snooze" action is chosen on the notification
+/// about it.
+async fn listen(
+    connection: &zbus::Connection,
+    alerts: &Arc<Mutex<Alerts>>,
+    ids: &Arc<Mutex<NotificationIds>>,
+) -> Result<(), GenericError> {
+    let rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .interface(NOTIFICATIONS_SERVICE)?
+        .member("ActionInvoked")?
+        .build();
+    let mut stream = MessageStream::for_match_rule(rule, connection, None).await?;
+
+    while let Some(message) = stream.next().await {
+        // One bad message shouldn't stop the snoozing.
+        let (id, action): (u32, String) = match message.and_then(|message| message.body()) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Could not read a notification action: {e}");
+                continue;
+            }
+        };
+        if action != SNOOZE_ACTION {
+            continue;
+        }
+        let source = lock(ids)?
+            .iter()
+            .find(|(_, known)| **known == id)
+            .map(|(source, _)| source.clone());
+        if let Some(source) = source {
+            lock(alerts)?.snooze_source(&source, SNOOZE);
+        }
+    }
+
+    Ok(())
+}
@@ -1,11 +1,5 @@
 use thiserror::Error;
 
-#[derive(Error, Debug)]
-#[error("Unrecognized metric unit type: {}", .units)]
-pub struct UnknownMetricUnitsError {
-    pub units: String,
-}
-
 #[derive(Error, Debug)]
 #[error("Metric is missing a property")]
 pub struct MissingPropertyError {}
@@ -13,3 +7,37 @@ pub struct MissingPropertyError {}
 #[derive(Error, Debug)]
 #[error("Metric metric had an unexpected type")]
 pub struct BadPropertyTypeError {}
+
+#[derive(Error, Debug)]
+#[error("No sensors found in {}", .source_name)]
+pub struct NoSensorsError {
+    pub source_name: String,
+}
+
+#[derive(Error, Debug)]
+#[error("No sensor data available. {}", .reasons)]
+pub struct NoBackendError {
+    pub reasons: String,
+}
+
+#[derive(Error, Debug)]
+#[error("No sensor with id {}", .id)]
+pub struct UnknownSensorError {
+    pub id: String,
+}
+
+#[derive(Error, Debug)]
+#[error("Could not join the {} thread", .name)]
+pub struct ThreadJoinError {
+    pub name: String,
+}
+
+#[derive(Error, Debug)]
+#[error("{} was started before discovering its sensors", .name)]
+pub struct NotDiscoveredError {
+    pub name: String,
+}
+
+#[derive(Error, Debug)]
+#[error("A filter rule needs either a glob or a regex, but not both")]
+pub struct BadRuleError {}
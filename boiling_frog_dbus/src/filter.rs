@@ -0,0 +1,216 @@
+use glob::Pattern;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::backend::Sensor;
+use crate::error::BadRuleError;
+use crate::GenericError;
+
+/// The part of a sensor that a [`Rule`] matches.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    /// The D-Bus object path or sysfs file.
+    Path,
+    /// The D-Bus interface name or hwmon chip name.
+    Interface,
+    Label,
+    /// The [`crate::simple_types::SensorKind::name`], e.g. "temp".
+    Kind,
+}
+
+#[derive(Clone, Debug)]
+enum Matcher {
+    /// Must match the whole field.
+    Glob(Pattern),
+    /// Can match any part of the field.
+    Regex(Regex),
+}
+
+/// Matches one field of a sensor against a glob or a regex.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "RuleConfig")]
+pub struct Rule {
+    field: Field,
+    matcher: Matcher,
+}
+
+impl Rule {
+    pub fn glob(field: Field, pattern: &str) -> Result<Rule, GenericError> {
+        Ok(Rule {
+            field,
+            matcher: Matcher::Glob(Pattern::new(pattern)?),
+        })
+    }
+
+    pub fn regex(field: Field, pattern: &str) -> Result<Rule, GenericError> {
+        Ok(Rule {
+            field,
+            matcher: Matcher::Regex(Regex::new(pattern)?),
+        })
+    }
+
+    pub fn matches(&self, sensor: &Sensor) -> bool {
+        let value = match self.field {
+            Field::Path => &sensor.path,
+            Field::Interface => &sensor.interface,
+            Field::Label => &sensor.label,
+            Field::Kind => sensor.kind.name(),
+        };
+        match &self.matcher {
+            Matcher::Glob(pattern) => pattern.matches(value),
+            Matcher::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// How a [`Rule`] is written in a config file, e.g. `{ field = "label", glob = "amdgpu temp3" }`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    field: Field,
+    glob: Option<String>,
+    regex: Option<String>,
+}
+
+impl TryFrom<RuleConfig> for Rule {
+    type Error = GenericError;
+
+    fn try_from(config: RuleConfig) -> Result<Self, Self::Error> {
+        match (config.glob, config.regex) {
+            (Some(pattern), None) => Rule::glob(config.field, &pattern),
+            (None, Some(pattern)) => Rule::regex(config.field, &pattern),
+            _ => Err(Box::new(BadRuleError {})),
+        }
+    }
+}
+
+/// Chooses the sensors that are read. A sensor is read if it matches any `include` rule, or there
+/// are none, and no `exclude` rule.
+///
+/// By default, Indicator Sensors' virtual sensors are excluded as they only repeat other sensors.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorFilter {
+    pub include: Vec<Rule>,
+    pub exclude: Vec<Rule>,
+}
+
+impl Default for SensorFilter {
+    fn default() -> Self {
+        SensorFilter {
+            include: vec![],
+            exclude: Rule::glob(Field::Path, "*/virtual/*").into_iter().collect(),
+        }
+    }
+}
+
+impl SensorFilter {
+    pub fn allows(&self, sensor: &Sensor) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|rule| rule.matches(sensor)))
+            && !self.exclude.iter().any(|rule| rule.matches(sensor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple_types::SensorKind;
+    use crate::units::Units;
+
+    const INTERFACE: &str = "com.github.alexmurray.IndicatorSensors.ActiveSensor";
+
+    fn sensor(path: &str, label: &str) -> Sensor {
+        Sensor {
+            id: path.to_string(),
+            path: path.to_string(),
+            interface: INTERFACE.to_string(),
+            label: label.to_string(),
+            kind: SensorKind::Temp,
+            units: Units::Celsius,
+        }
+    }
+
+    fn cpu() -> Sensor {
+        sensor("/ActiveSensors/hwmon/k10temp/temp1", "CPU Tctl")
+    }
+
+    fn virtual_sensor() -> Sensor {
+        sensor("/ActiveSensors/virtual/max", "Maximum")
+    }
+
+    fn filter(config: &str) -> Result<SensorFilter, toml::de::Error> {
+        toml::from_str(config)
+    }
+
+    #[test]
+    fn globs_match_the_whole_field() {
+        assert!(Rule::glob(Field::Label, "CPU*").unwrap().matches(&cpu()));
+        assert!(!Rule::glob(Field::Label, "Tctl").unwrap().matches(&cpu()));
+        assert!(Rule::glob(Field::Path, "*/k10temp/*")
+            .unwrap()
+            .matches(&cpu()));
+    }
+
+    #[test]
+    fn regexes_match_any_part_of_the_field() {
+        assert!(Rule::regex(Field::Label, "Tctl").unwrap().matches(&cpu()));
+        assert!(!Rule::regex(Field::Label, "^Tctl").unwrap().matches(&cpu()));
+        assert!(Rule::regex(Field::Interface, r"\.ActiveSensor$")
+            .unwrap()
+            .matches(&cpu()));
+        assert!(Rule::regex(Field::Kind, "^temp$").unwrap().matches(&cpu()));
+    }
+
+    #[test]
+    fn bad_patterns_are_rejected() {
+        assert!(Rule::glob(Field::Label, "[").is_err());
+        assert!(Rule::regex(Field::Label, "(").is_err());
+    }
+
+    #[test]
+    fn excludes_virtual_sensors_by_default() {
+        let filter = SensorFilter::default();
+
+        assert!(filter.allows(&cpu()));
+        assert!(!filter.allows(&virtual_sensor()));
+    }
+
+    #[test]
+    fn includes_only_matching_sensors() {
+        let filter = filter(r#"include = [{ field = "label", glob = "GPU*" }]"#).unwrap();
+
+        assert!(!filter.allows(&cpu()));
+        assert!(filter.allows(&sensor("/ActiveSensors/gpu", "GPU edge")));
+    }
+
+    #[test]
+    fn setting_only_include_keeps_the_default_exclude() {
+        let filter =
+            filter(r#"include = [{ field = "path", glob = "/ActiveSensors/*" }]"#).unwrap();
+
+        assert!(filter.allows(&cpu()));
+        assert!(!filter.allows(&virtual_sensor()));
+    }
+
+    #[test]
+    fn setting_exclude_replaces_the_default() {
+        let filter = filter(r#"exclude = [{ field = "label", regex = "Tctl" }]"#).unwrap();
+
+        assert!(!filter.allows(&cpu()));
+        assert!(filter.allows(&virtual_sensor()));
+    }
+
+    #[test]
+    fn rules_need_exactly_one_pattern() {
+        assert!(filter(r#"include = [{ field = "label" }]"#).is_err());
+        assert!(filter(r#"include = [{ field = "label", glob = "a", regex = "b" }]"#).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(filter(r#"include = [{ field = "label", glob = "a", case = "ignore" }]"#).is_err());
+        assert!(filter(r#"includes = [{ field = "label", glob = "a" }]"#).is_err());
+        assert!(filter(r#"include = [{ field = "name", glob = "a" }]"#).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How much history the engine keeps for each sensor & aggregate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HistoryLimits {
+    /// How long every reading is kept for. Defaults to 30 minutes.
+    pub full_resolution: Duration,
+    /// How long the buckets that older readings are averaged into are. Defaults to 1 minute.
+    pub bucket: Duration,
+    /// How long readings are kept for in all. Defaults to 24 hours.
+    pub retention: Duration,
+    /// The most readings kept at full resolution, in case a sensor updates very often. Older
+    /// ones are bucketed early. Defaults to 10000.
+    pub max_samples: usize,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        HistoryLimits {
+            full_resolution: Duration::from_secs(30 * 60),
+            bucket: Duration::from_secs(60),
+            retention: Duration::from_secs(24 * 60 * 60),
+            max_samples: 10_000,
+        }
+    }
+}
+
+/// A reading, or the average of a bucket of readings, in canonical units. A value holds until the
+/// next sample.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    /// When the reading was taken, or when the bucket's first reading was.
+    pub time: Instant,
+    /// The reading, or the mean of the bucket's readings.
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Sample {
+    fn new(time: Instant, value: f64) -> Sample {
+        Sample {
+            time,
+            value,
+            min: value,
+            max: value,
+        }
+    }
+}
+
+struct Bucket {
+    sample: Sample,
+    count: u32,
+}
+
+/// The readings from one sensor or aggregate, oldest first: recent ones at full resolution,
+/// older ones averaged into buckets.
+pub(crate) struct History {
+    buckets: VecDeque<Bucket>,
+    limits: HistoryLimits,
+    recent: VecDeque<Sample>,
+}
+
+impl History {
+    pub(crate) fn new(limits: &HistoryLimits) -> History {
+        History {
+            buckets: VecDeque::new(),
+            limits: *limits,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, time: Instant, value: f64) {
+        self.recent.push_back(Sample::new(time, value));
+
+        while let Some(oldest) = self.recent.front() {
+            let is_old = time.saturating_duration_since(oldest.time) > self.limits.full_resolution;
+            if !is_old && self.recent.len() <= self.limits.max_samples {
+                break;
+            }
+            if let Some(oldest) = self.recent.pop_front() {
+                self.add_to_bucket(oldest);
+            }
+        }
+
+        while self.buckets.front().is_some_and(|bucket| {
+            time.saturating_duration_since(bucket.sample.time) > self.limits.retention
+        }) {
+            self.buckets.pop_front();
+        }
+    }
+
+    /// The samples taken from `from` to `to`, oldest first, preceded by the one that was current
+    /// at `from` if there is one.
+    pub(crate) fn range(&self, from: Instant, to: Instant) -> Vec<Sample> {
+        let mut current = None;
+        let mut samples = vec![];
+        let all = self
+            .buckets
+            .iter()
+            .map(|bucket| &bucket.sample)
+            .chain(self.recent.iter());
+        for sample in all {
+            if sample.time < from {
+                current = Some(*sample);
+            } else if sample.time <= to {
+                samples.push(*sample);
+            } else {
+                break;
+            }
+        }
+
+        current.into_iter().chain(samples).collect()
+    }
+
+    fn add_to_bucket(&mut self, sample: Sample) {
+        match self.buckets.back_mut() {
+            Some(bucket) if sample.time < bucket.sample.time + self.limits.bucket => {
+                let count = f64::from(bucket.count);
+                bucket.sample.value = (bucket.sample.value * count + sample.value) / (count + 1.0);
+                bucket.sample.min = bucket.sample.min.min(sample.min);
+                bucket.sample.max = bucket.sample.max.max(sample.max);
+                bucket.count += 1;
+            }
+            _ => self.buckets.push_back(Bucket { sample, count: 1 }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn limits(
+        full_resolution: u64,
+        bucket: u64,
+        retention: u64,
+        max_samples: usize,
+    ) -> HistoryLimits {
+        HistoryLimits {
+            full_resolution: Duration::from_secs(full_resolution),
+            bucket: Duration::from_secs(bucket),
+            retention: Duration::from_secs(retention),
+            max_samples,
+        }
+    }
+
+    /// Pushes `values` a second apart, starting at `start`.
+    fn history(limits: &HistoryLimits, start: Instant, values: &[f64]) -> History {
+        let mut history = History::new(limits);
+        for (second, value) in values.iter().enumerate() {
+            history.push(start + SECOND * second as u32, *value);
+        }
+        history
+    }
+
+    fn all(history: &History, start: Instant) -> Vec<Sample> {
+        history.range(start, start + SECOND * 3600)
+    }
+
+    #[test]
+    fn keeps_recent_readings_at_full_resolution() {
+        let start = Instant::now();
+        let history = history(&limits(10, 5, 100, 100), start, &[1.0, 2.0, 3.0]);
+
+        assert_eq!(
+            all(&history, start),
+            vec![
+                Sample::new(start, 1.0),
+                Sample::new(start + SECOND, 2.0),
+                Sample::new(start + SECOND * 2, 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn averages_old_readings_into_buckets() {
+        let start = Instant::now();
+        // Readings older than 2 seconds go into 3 second buckets.
+        let values = [1.0, 5.0, 3.0, 10.0, 20.0, 30.0];
+        let history = history(&limits(2, 3, 100, 100), start, &values);
+
+        let samples = all(&history, start);
+        assert_eq!(
+            samples[0],
+            Sample {
+                time: start,
+                value: 3.0,
+                min: 1.0,
+                max: 5.0,
+            }
+        );
+        assert_eq!(
+            samples[1..],
+            [
+                Sample::new(start + SECOND * 3, 10.0),
+                Sample::new(start + SECOND * 4, 20.0),
+                Sample::new(start + SECOND * 5, 30.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn buckets_the_oldest_readings_beyond_max_samples() {
+        let start = Instant::now();
+        let history = history(&limits(100, 10, 1000, 2), start, &[1.0, 2.0, 3.0, 4.0]);
+
+        let samples = all(&history, start);
+        assert_eq!(
+            samples,
+            vec![
+                Sample {
+                    time: start,
+                    value: 1.5,
+                    min: 1.0,
+                    max: 2.0,
+                },
+                Sample::new(start + SECOND * 2, 3.0),
+                Sample::new(start + SECOND * 3, 4.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn forgets_buckets_past_retention() {
+        let start = Instant::now();
+        let mut history = history(&limits(1, 1, 5, 100), start, &[1.0, 2.0, 3.0]);
+        history.push(start + SECOND * 10, 4.0);
+
+        let samples = all(&history, start);
+        assert_eq!(samples, vec![Sample::new(start + SECOND * 10, 4.0)]);
+    }
+
+    #[test]
+    fn starts_ranges_with_the_reading_current_at_from() {
+        let start = Instant::now();
+        let history = history(&limits(100, 10, 1000, 100), start, &[1.0, 2.0, 3.0, 4.0]);
+
+        assert_eq!(
+            history.range(start + SECOND + SECOND / 2, start + SECOND * 2),
+            vec![
+                Sample::new(start + SECOND, 2.0),
+                Sample::new(start + SECOND * 2, 3.0),
+            ]
+        );
+        assert_eq!(
+            history.range(start + SECOND * 10, start + SECOND * 20),
+            vec![Sample::new(start + SECOND * 3, 4.0)]
+        );
+        assert!(history.range(start - SECOND * 2, start - SECOND).is_empty());
+    }
+}
@@ -0,0 +1,489 @@
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{sleep, spawn, JoinHandle};
+
+use log::{debug, info};
+
+use crate::backend::{Sensor, SensorBackend, SensorSink};
+use crate::config::{HWMON_PATH, HWMON_POLL_RATE};
+use crate::error::{NoSensorsError, ThreadJoinError};
+use crate::simple_types::SensorKind;
+use crate::units::Units;
+use crate::GenericError;
+
+/// A single `*_input` file under a hwmon chip directory.
+#[derive(Clone, Debug)]
+struct HwmonSensor {
+    input_path: PathBuf,
+    chip_name: String,
+    kind: SensorKind,
+    label: String,
+    /// What the kernel's fixed point value must be divided by to give a value in `kind.units()`.
+    divisor: f64,
+}
+
+impl HwmonSensor {
+    fn read(&self) -> Result<f64, GenericError> {
+        let raw = read_to_string(&self.input_path)?.trim().parse::<f64>()?;
+        Ok(raw / self.divisor)
+    }
+
+    fn id(&self) -> String {
+        self.input_path.display().to_string()
+    }
+
+    fn sensor(&self) -> Sensor {
+        Sensor {
+            id: self.id(),
+            path: self.id(),
+            interface: self.chip_name.clone(),
+            label: self.label.clone(),
+            kind: self.kind,
+            units: self.units(),
+        }
+    }
+
+    fn units(&self) -> Units {
+        self.kind.units()
+    }
+}
+
+/// Reads temperatures, fan speeds, voltages, currents, power, energy & frequencies straight from
+/// the kernel's hwmon sysfs interface, for machines that don't run Indicator Sensors.
+#[derive(Debug)]
+pub struct HwmonSession {
+    root: PathBuf,
+    sensors: Vec<HwmonSensor>,
+    running: Arc<AtomicBool>,
+    poller: Option<JoinHandle<()>>,
+}
+
+impl Default for HwmonSession {
+    fn default() -> Self {
+        HwmonSession::with_root(Path::new(HWMON_PATH))
+    }
+}
+
+impl HwmonSession {
+    pub fn new() -> HwmonSession {
+        HwmonSession::default()
+    }
+
+    /// Reads hwmon chips from `root` rather than `/sys/class/hwmon`.
+    pub fn with_root(root: &Path) -> HwmonSession {
+        HwmonSession {
+            root: root.to_path_buf(),
+            sensors: vec![],
+            running: Arc::new(AtomicBool::new(false)),
+            poller: None,
+        }
+    }
+}
+
+impl SensorBackend for HwmonSession {
+    fn name(&self) -> &str {
+        "hwmon"
+    }
+
+    fn discover(&mut self) -> Result<Vec<Sensor>, GenericError> {
+        self.sensors = discover(&self.root)?;
+        if self.sensors.is_empty() {
+            return Err(Box::new(NoSensorsError {
+                source_name: self.root.display().to_string(),
+            }));
+        }
+
+        for sensor in &self.sensors {
+            debug!("hwmon sensor = {:?}", sensor);
+        }
+
+        Ok(self.sensors.iter().map(HwmonSensor::sensor).collect())
+    }
+
+    /// Indicator Sensors reads the same chips, so hwmon only reports while it isn't running.
+    fn is_fallback(&self) -> bool {
+        true
+    }
+
+    fn start(&mut self, sink: SensorSink) -> Result<(), GenericError> {
+        self.running.store(true, Ordering::SeqCst);
+        // Added straight away if they're needed, so that they're known once the engine is built.
+        let is_added = !sink.is_primary_available();
+        if is_added {
+            add_all(&self.sensors, &sink)?;
+        }
+        let sensors = self.sensors.clone();
+        let running = self.running.clone();
+        self.poller = Some(spawn(move || poll(&sensors, &sink, &running, is_added)));
+
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), GenericError> {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(poller) = self.poller.take() {
+            poller.join().map_err(|_| ThreadJoinError {
+                name: self.name().to_string(),
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+fn discover(root: &Path) -> Result<Vec<HwmonSensor>, GenericError> {
+    let mut sensors = Vec::new();
+    for chip in read_dir(root)? {
+        let chip_path = chip?.path();
+        let chip_name = read_trimmed(&chip_path.join("name")).unwrap_or_else(|| {
+            chip_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+
+        for file in read_dir(&chip_path)? {
+            let file_name = file?.file_name().to_string_lossy().to_string();
+            if let Some(sensor) = sensor_from(&chip_path, &chip_name, &file_name) {
+                // Some drivers expose inputs that always fail to read, e.g. for absent fans.
+                if sensor.read().is_ok() {
+                    sensors.push(sensor);
+                }
+            }
+        }
+    }
+
+    sensors.sort_by(|left, right| left.input_path.cmp(&right.input_path));
+    Ok(sensors)
+}
+
+fn sensor_from(chip_path: &Path, chip_name: &str, file_name: &str) -> Option<HwmonSensor> {
+    let channel = file_name.strip_suffix("_input").or_else(|| {
+        // Many GPUs only report their average power draw.
+        file_name
+            .strip_suffix("_average")
+            .filter(|channel| channel.starts_with("power"))
+            .filter(|channel| !chip_path.join(format!("{channel}_input")).exists())
+    })?;
+    let (kind, divisor) = channel_type(channel.trim_end_matches(|c: char| c.is_ascii_digit()))?;
+    let label = read_trimmed(&chip_path.join(format!("{channel}_label")))
+        .unwrap_or_else(|| channel.to_string());
+
+    Some(HwmonSensor {
+        input_path: chip_path.join(file_name),
+        chip_name: chip_name.to_string(),
+        kind,
+        label: format!("{chip_name} {label}"),
+        divisor,
+    })
+}
+
+/// The kind of sensor behind each hwmon channel type, with the divisor that converts the kernel's
+/// units to that kind's units.
+fn channel_type(channel_type: &str) -> Option<(SensorKind, f64)> {
+    match channel_type {
+        // Millidegrees Celsius.
+        "temp" => Some((SensorKind::Temp, 1000.0)),
+        "fan" => Some((SensorKind::Fan, 1.0)),
+        // Millivolts.
+        "in" => Some((SensorKind::Voltage, 1000.0)),
+        // Milliamps.
+        "curr" => Some((SensorKind::Current, 1000.0)),
+        // Microwatts.
+        "power" => Some((SensorKind::Power, 1_000_000.0)),
+        // Microjoules.
+        "energy" => Some((SensorKind::Energy, 1_000_000.0)),
+        // Hertz.
+        "freq" => Some((SensorKind::Frequency, 1_000_000.0)),
+        _ => None,
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    read_to_string(path)
+        .ok()
+        .map(|contents| contents.trim().to_string())
+        .filter(|contents| !contents.is_empty())
+}
+
+fn add_all(sensors: &[HwmonSensor], sink: &SensorSink) -> Result<(), GenericError> {
+    for sensor in sensors {
+        sink.add(sensor.sensor())?;
+    }
+    Ok(())
+}
+
+/// Reads every sensor until `running` is cleared, removing them all while another backend's
+/// service is available & adding them back when it goes away.
+fn poll(sensors: &[HwmonSensor], sink: &SensorSink, running: &AtomicBool, mut is_added: bool) {
+    while running.load(Ordering::SeqCst) {
+        let stand_aside = sink.is_primary_available();
+        if is_added && stand_aside {
+            info!("hwmon standing aside for another backend");
+            for sensor in sensors {
+                if let Err(e) = sink.remove(&sensor.id()) {
+                    debug!("hwmon removal of {}: {e}", sensor.input_path.display());
+                }
+            }
+            is_added = false;
+        } else if !is_added && !stand_aside {
+            info!("hwmon taking over from another backend");
+            match add_all(sensors, sink) {
+                Ok(()) => is_added = true,
+                Err(e) => debug!("hwmon could not add its sensors: {e}"),
+            }
+        }
+
+        for sensor in sensors.iter().filter(|_| is_added) {
+            let result = sensor
+                .read()
+                .and_then(|value| sink.update(&sensor.id(), value, &sensor.units()));
+            if let Err(e) = result {
+                debug!("hwmon read of {}: {e}", sensor.input_path.display());
+            }
+        }
+
+        sleep(HWMON_POLL_RATE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir, write};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::dbus_engine::DbusEngine;
+
+    /// Writes each `(file, contents)` into the chip directory `chip` under `root`.
+    fn chip(root: &Path, chip: &str, files: &[(&str, &str)]) {
+        let chip_path = root.join(chip);
+        create_dir(&chip_path).unwrap();
+        for (file, contents) in files {
+            write(chip_path.join(file), contents).unwrap();
+        }
+    }
+
+    fn found(root: &Path) -> Vec<(String, String, SensorKind, f64)> {
+        discover(root)
+            .unwrap()
+            .iter()
+            .map(|sensor| {
+                let file = sensor.input_path.file_name().unwrap();
+                (
+                    file.to_string_lossy().to_string(),
+                    sensor.label.clone(),
+                    sensor.kind,
+                    sensor.read().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn labels_sensors_by_chip_name_and_channel_label() {
+        let root = TempDir::new().unwrap();
+        chip(
+            root.path(),
+            "hwmon0",
+            &[
+                ("name", "k10temp\n"),
+                ("temp1_input", "45000\n"),
+                ("temp1_label", "Tctl\n"),
+                ("temp2_input", "50000\n"),
+            ],
+        );
+        chip(root.path(), "hwmon1", &[("fan1_input", "2000\n")]);
+
+        let sensors = discover(root.path()).unwrap();
+        let labels: Vec<(&str, &str)> = sensors
+            .iter()
+            .map(|sensor| (sensor.chip_name.as_str(), sensor.label.as_str()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("k10temp", "k10temp Tctl"),
+                ("k10temp", "k10temp temp2"),
+                ("hwmon1", "hwmon1 fan1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn divides_fixed_point_values_into_each_kinds_units() {
+        let root = TempDir::new().unwrap();
+        chip(
+            root.path(),
+            "hwmon0",
+            &[
+                ("name", "chip"),
+                ("curr1_input", "1500"),
+                ("energy1_input", "2500000"),
+                ("fan1_input", "1800"),
+                ("freq1_input", "1200000000"),
+                ("in0_input", "1100"),
+                ("power1_input", "15000000"),
+                ("temp1_input", "42500"),
+            ],
+        );
+
+        let found: Vec<(SensorKind, f64)> = found(root.path())
+            .into_iter()
+            .map(|(_, _, kind, value)| (kind, value))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (SensorKind::Current, 1.5),
+                (SensorKind::Energy, 2.5),
+                (SensorKind::Fan, 1800.0),
+                (SensorKind::Frequency, 1200.0),
+                (SensorKind::Voltage, 1.1),
+                (SensorKind::Power, 15.0),
+                (SensorKind::Temp, 42.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_average_power_only_without_an_input() {
+        let root = TempDir::new().unwrap();
+        chip(
+            root.path(),
+            "hwmon0",
+            &[
+                ("name", "amdgpu"),
+                ("power1_average", "30000000"),
+                ("power1_label", "PPT"),
+                ("power2_average", "1000000"),
+                ("power2_input", "2000000"),
+                ("temp1_average", "40000"),
+            ],
+        );
+
+        assert_eq!(
+            found(root.path()),
+            vec![
+                (
+                    "power1_average".to_string(),
+                    "amdgpu PPT".to_string(),
+                    SensorKind::Power,
+                    30.0
+                ),
+                (
+                    "power2_input".to_string(),
+                    "amdgpu power2".to_string(),
+                    SensorKind::Power,
+                    2.0
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_inputs_that_cannot_be_read() {
+        let root = TempDir::new().unwrap();
+        chip(
+            root.path(),
+            "hwmon0",
+            &[
+                ("name", "nct6775"),
+                ("fan1_input", "900"),
+                ("fan2_input", ""),
+                ("fan3_input", "not a number"),
+                ("pwm1", "128"),
+                ("temp1_max", "80000"),
+            ],
+        );
+        create_dir(root.path().join("hwmon0").join("fan4_input")).unwrap();
+
+        let files: Vec<String> = found(root.path())
+            .into_iter()
+            .map(|(file, _, _, _)| file)
+            .collect();
+        assert_eq!(files, vec!["fan1_input".to_string()]);
+    }
+
+    #[test]
+    fn has_no_sensors_without_chips() {
+        let root = TempDir::new().unwrap();
+
+        assert!(HwmonSession::with_root(root.path()).discover().is_err());
+    }
+
+    /// Stands in for Indicator Sensors, keeping its sink so that it can come & go.
+    struct Primary {
+        sink: Arc<Mutex<Option<SensorSink>>>,
+    }
+
+    impl SensorBackend for Primary {
+        fn name(&self) -> &str {
+            "primary"
+        }
+
+        fn discover(&mut self) -> Result<Vec<Sensor>, GenericError> {
+            Ok(vec![])
+        }
+
+        fn start(&mut self, sink: SensorSink) -> Result<(), GenericError> {
+            sink.set_primary_available(true);
+            *self.sink.lock().unwrap() = Some(sink);
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> Result<(), GenericError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stands_aside_while_the_primary_backend_is_available() {
+        let root = TempDir::new().unwrap();
+        chip(root.path(), "hwmon0", &[("temp1_input", "45000")]);
+        let sink = Arc::new(Mutex::new(None));
+        let engine = DbusEngine::builder()
+            .with_backend(Box::new(HwmonSession::with_root(root.path())))
+            .and()
+            .with_backend(Box::new(Primary { sink: sink.clone() }))
+            .and()
+            .build()
+            .unwrap();
+        assert!(engine.sensors().unwrap().is_empty());
+
+        sink.lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .set_primary_available(false);
+        let deadline = Instant::now() + HWMON_POLL_RATE * 3;
+        while engine.sensors().unwrap().is_empty() && Instant::now() < deadline {
+            sleep(Duration::from_millis(50));
+        }
+        let labels: Vec<String> = engine
+            .sensors()
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| snapshot.label)
+            .collect();
+        assert_eq!(labels, vec!["hwmon0 temp1".to_string()]);
+    }
+
+    #[test]
+    fn reads_sensors_without_a_primary_backend() {
+        let root = TempDir::new().unwrap();
+        chip(root.path(), "hwmon0", &[("temp1_input", "45000")]);
+        let engine = DbusEngine::builder()
+            .with_backend(Box::new(HwmonSession::with_root(root.path())))
+            .and()
+            .build()
+            .unwrap();
+
+        assert_eq!(engine.sensors().unwrap().len(), 1);
+    }
+}
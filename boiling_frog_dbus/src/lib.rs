@@ -1,14 +1,89 @@
+//! Reads a computer's sensors & aggregates them into one reading per kind, e.g. the highest
+//! temperature.
+//!
+//! A [`DbusEngine`] is built from one or more [`SensorBackend`]s. [`DbusSession`] reads
+//! Indicator Sensors over D-Bus & needs the `dbus` feature; [`HwmonSession`] reads the kernel's
+//! hwmon sensors from sysfs & needs the `hwmon` feature. Both are enabled by default, and other
+//! backends can be added by implementing [`SensorBackend`].
+//!
+//! ```no_run
+//! use boiling_frog_dbus::{Aggregation, DbusEngine, SensorKind};
+//!
+//! let engine = DbusEngine::builder()
+//!     .with_default_backends()
+//!     .and()
+//!     .with_aggregation(SensorKind::Temp, Aggregation::Mean)
+//!     .and()
+//!     .build()?;
+//! let reading = engine.reading(SensorKind::Temp)?;
+//! println!("{} {}{}", reading.label, reading.value, reading.units);
+//! # Ok::<(), boiling_frog_dbus::GenericError>(())
+//! ```
+//!
+//! Everything the library offers is re-exported here, from modules that are private so that they
+//! can be reorganised.
+
 use std::error::Error;
 
-pub mod dbus_engine;
-pub mod mutex_helpers;
-pub mod simple_types;
 pub type GenericError = Box<dyn Error + Send + Sync>;
 
+pub use aggregation::Aggregation;
+pub use alerts::{Alert, AlertCallback, AlertCause, AlertSettings, AlertSource, SNOOZE};
+pub use backend::{Sensor, SensorBackend, SensorSink};
+pub use baseline::{Baseline, Condition, Drift, Load, FAN_BAND};
+pub use config::{ACTIVE_SENSORS_PATH, INDICATOR_SENSORS_SERVICE};
+#[cfg(feature = "sqlite")]
+pub use database::{
+    aggregate_id, Database, DatabaseSettings, Resolution, Retention, StoredSample, StoredSensor,
+};
+pub use dbus_engine::{DbusEngine, DbusEngineBuilder};
+#[cfg(feature = "dbus")]
+pub use dbus_session::DbusSession;
+#[cfg(feature = "dbus")]
+pub use desktop_notifications::AlertDescriber;
+pub use error::{
+    BadPropertyTypeError, BadRuleError, MissingPropertyError, NoBackendError, NoSensorsError,
+    NotDiscoveredError, ThreadJoinError, UnknownSensorError,
+};
+pub use filter::{Field, Rule, SensorFilter};
+pub use history::{HistoryLimits, Sample};
+#[cfg(feature = "hwmon")]
+pub use hwmon_session::HwmonSession;
+pub use mutex_helpers::LockError;
+pub use simple_types::{
+    Reading, ReadingStatus, SensorCallback, SensorKind, SensorSnapshot, SubscriptionId,
+};
+pub use thresholds::{Level, Thresholds};
+pub use trend::{Trend, TrendSettings};
+pub use units::{TemperatureScale, Units};
+
+mod aggregation;
+mod alerts;
+mod backend;
+mod baseline;
 mod config;
+#[cfg(feature = "sqlite")]
+mod database;
+mod dbus_engine;
+#[cfg(feature = "dbus")]
 mod dbus_info;
+#[cfg(feature = "dbus")]
 mod dbus_session;
+#[cfg(feature = "dbus")]
+mod desktop_notifications;
 mod error;
+mod filter;
+mod history;
+#[cfg(feature = "hwmon")]
+mod hwmon_session;
+#[cfg(feature = "dbus")]
 mod metric;
+#[cfg(feature = "dbus")]
 mod metric_value;
+mod mutex_helpers;
+mod sensor_registry;
+mod simple_types;
 mod sorted_property_observer;
+mod thresholds;
+mod trend;
+mod units;
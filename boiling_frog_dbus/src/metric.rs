@@ -1,33 +1,24 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 
+use crate::backend::Sensor;
 use crate::dbus_info::DbusInfo;
-use crate::error::UnknownMetricUnitsError;
 use zbus::zvariant::OwnedValue;
 
 use crate::metric_value::MetricValue;
+use crate::simple_types::SensorKind;
 use crate::GenericError;
 
+/// An Indicator Sensors sensor, classified by the units it reports in.
 #[derive(Clone, Debug)]
-pub(crate) enum Metric {
-    Fan(MetricValue),
-    Temp(MetricValue),
+pub(crate) struct Metric {
+    kind: SensorKind,
+    value: MetricValue,
 }
 
 impl Display for Metric {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Metric::Fan(value) => {
-                let _ = f.write_str("Fan { ");
-                let _ = write!(f, "{:?}", value);
-                f.write_str(" }")
-            }
-            Metric::Temp(value) => {
-                let _ = f.write_str("Temp { ");
-                let _ = write!(f, "{:?}", value);
-                f.write_str(" }")
-            }
-        }
+        write!(f, "{:?} {{ {:?} }}", self.kind, self.value)
     }
 }
 
@@ -36,7 +27,7 @@ impl Metric {
         dbus_info: &DbusInfo,
         map: &HashMap<String, OwnedValue>,
     ) -> Option<Metric> {
-        return Metric::metric_from(dbus_info, map).ok();
+        Metric::metric_from(dbus_info, map).ok()
     }
 
     pub(crate) fn metric_from(
@@ -44,22 +35,25 @@ impl Metric {
         map: &HashMap<String, OwnedValue>,
     ) -> Result<Metric, GenericError> {
         let value = MetricValue::value_from(dbus_info, map)?;
-        match (&value.units).trim() {
-            "RPM" => Ok(Metric::Fan(value)),
-            "℃" => Ok(Metric::Temp(value)),
-            "°C" => Ok(Metric::Temp(value)),
-            "℉" => Ok(Metric::Temp(value)),
-            "°F" => Ok(Metric::Temp(value)),
-            _ => Err(Box::new(UnknownMetricUnitsError {
-                units: value.units.clone(),
-            })),
-        }
+        Ok(Metric {
+            kind: value.units.kind(),
+            value,
+        })
     }
 
     pub fn get_value(&self) -> &MetricValue {
-        match self {
-            Metric::Fan(value) => value,
-            Metric::Temp(value) => value,
+        &self.value
+    }
+
+    pub(crate) fn sensor(&self) -> Sensor {
+        let value = self.get_value();
+        Sensor {
+            id: value.dbus_info.path.clone(),
+            path: value.dbus_info.path.clone(),
+            interface: value.dbus_info.interface_name.clone(),
+            label: value.label.clone(),
+            kind: self.kind,
+            units: value.units.clone(),
         }
     }
 }
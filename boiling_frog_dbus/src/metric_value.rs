@@ -1,5 +1,6 @@
 use crate::dbus_info::DbusInfo;
 use crate::error::{BadPropertyTypeError, MissingPropertyError};
+use crate::units::Units;
 use crate::GenericError;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
@@ -18,7 +19,7 @@ macro_rules! thing_from {
 pub(crate) struct MetricValue {
     pub dbus_info: DbusInfo,
     pub label: String,
-    pub units: String,
+    pub units: Units,
     pub value: f64,
 }
 
@@ -34,14 +35,14 @@ impl MetricValue {
         map: &HashMap<String, OwnedValue>,
     ) -> Result<MetricValue, GenericError> {
         let label = thing_from!(Str, map.get("Label"))?.to_string();
-        let units = thing_from!(Str, map.get("Units"))?.to_string();
+        let units = Units::from(thing_from!(Str, map.get("Units"))?.as_str());
         let value = thing_from!(f64, map.get("Value"))?;
 
         Ok(MetricValue {
             dbus_info: dbus_info.clone(),
             label,
             units,
-            value: value.clone(),
+            value: *value,
         })
     }
 }
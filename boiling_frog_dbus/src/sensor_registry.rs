@@ -0,0 +1,208 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use log::debug;
+
+use crate::backend::Sensor;
+use crate::filter::SensorFilter;
+use crate::history::{History, HistoryLimits, Sample};
+use crate::simple_types::{ReadingStatus, SensorCallback, SensorSnapshot, SubscriptionId};
+use crate::units::Units;
+
+/// Every sensor the backends know about, with its latest reading, its history & anyone subscribed
+/// to it or to every sensor.
+///
+/// Sensors the filter excludes are hidden, but their readings are kept in case the filter changes.
+pub(crate) struct SensorRegistry {
+    all_subscriptions: Vec<(SubscriptionId, SensorCallback)>,
+    excluded: HashSet<String>,
+    filter: SensorFilter,
+    histories: HashMap<String, History>,
+    history_limits: HistoryLimits,
+    next_subscription: u64,
+    sensors: HashMap<String, Sensor>,
+    snapshots: HashMap<String, SensorSnapshot>,
+    subscriptions: HashMap<String, Vec<(SubscriptionId, SensorCallback)>>,
+}
+
+impl SensorRegistry {
+    pub(crate) fn new(filter: &SensorFilter, history_limits: &HistoryLimits) -> SensorRegistry {
+        SensorRegistry {
+            all_subscriptions: vec![],
+            excluded: HashSet::new(),
+            filter: filter.clone(),
+            histories: HashMap::new(),
+            history_limits: *history_limits,
+            next_subscription: 0,
+            sensors: HashMap::new(),
+            snapshots: HashMap::new(),
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, sensor: Sensor) {
+        if !self.filter.allows(&sensor) {
+            debug!("{} filtered out", sensor.path);
+            self.excluded.insert(sensor.id.clone());
+        }
+        self.snapshots.insert(
+            sensor.id.clone(),
+            SensorSnapshot {
+                id: sensor.id.clone(),
+                label: sensor.label.clone(),
+                kind: sensor.kind,
+                units: sensor.units.clone(),
+                value: None,
+                updated: None,
+                status: ReadingStatus::NeverReceived,
+            },
+        );
+        self.sensors.insert(sensor.id.clone(), sensor);
+    }
+
+    pub(crate) fn is_excluded(&self, id: &str) -> bool {
+        self.excluded.contains(id)
+    }
+
+    pub(crate) fn remove(&mut self, id: &str) -> Option<SensorSnapshot> {
+        self.excluded.remove(id);
+        self.histories.remove(id);
+        self.sensors.remove(id);
+        self.snapshots.remove(id)
+    }
+
+    /// Applies a new filter, returning the sensors it includes that were excluded, & the ones it
+    /// excludes that were included.
+    pub(crate) fn set_filter(
+        &mut self,
+        filter: &SensorFilter,
+    ) -> (Vec<SensorSnapshot>, Vec<SensorSnapshot>) {
+        self.filter = filter.clone();
+        let mut included = Vec::new();
+        let mut excluded = Vec::new();
+        for (id, sensor) in &self.sensors {
+            let allowed = self.filter.allows(sensor);
+            let was_allowed = !self.excluded.contains(id);
+            if allowed == was_allowed {
+                continue;
+            }
+
+            if allowed {
+                self.excluded.remove(id);
+                included.extend(self.snapshots.get(id).cloned());
+            } else {
+                self.excluded.insert(id.clone());
+                excluded.extend(self.snapshots.get(id).cloned());
+            }
+        }
+
+        (included, excluded)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.snapshots.keys().all(|id| self.excluded.contains(id))
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<&SensorSnapshot> {
+        self.snapshots.get(id).filter(|_| !self.is_excluded(id))
+    }
+
+    /// The readings from the sensor with `id` between `from` & `to`, as [`History::range`].
+    pub(crate) fn history(&self, id: &str, from: Instant, to: Instant) -> Option<Vec<Sample>> {
+        self.histories
+            .get(id)
+            .filter(|_| !self.is_excluded(id))
+            .map(|history| history.range(from, to))
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn history_limits(&self) -> &HistoryLimits {
+        &self.history_limits
+    }
+
+    /// Starts the history of the sensor with `id`, unless it already has one.
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn start_history(&mut self, id: &str) -> Option<&mut History> {
+        if self.histories.contains_key(id) {
+            return None;
+        }
+        Some(
+            self.histories
+                .entry(id.to_string())
+                .or_insert_with(|| History::new(&self.history_limits)),
+        )
+    }
+
+    /// All snapshots, ordered by label.
+    pub(crate) fn snapshots(&self) -> Vec<SensorSnapshot> {
+        let mut snapshots: Vec<SensorSnapshot> = self
+            .snapshots
+            .values()
+            .filter(|snapshot| !self.is_excluded(&snapshot.id))
+            .cloned()
+            .collect();
+        snapshots.sort_by(|left, right| {
+            left.label
+                .cmp(&right.label)
+                .then_with(|| left.id.cmp(&right.id))
+        });
+        snapshots
+    }
+
+    /// Records a new reading, returning the updated snapshot & the callbacks subscribed to it.
+    pub(crate) fn update(
+        &mut self,
+        id: &str,
+        value: f64,
+        units: &Units,
+    ) -> Option<(SensorSnapshot, Vec<SensorCallback>)> {
+        let snapshot = self.snapshots.get_mut(id)?;
+        let now = Instant::now();
+        snapshot.value = Some(value);
+        snapshot.units = units.clone();
+        snapshot.updated = Some(now);
+        snapshot.status = ReadingStatus::Fresh;
+        self.histories
+            .entry(id.to_string())
+            .or_insert_with(|| History::new(&self.history_limits))
+            .push(now, value);
+        let callbacks = self
+            .subscriptions
+            .get(id)
+            .into_iter()
+            .flatten()
+            .chain(&self.all_subscriptions)
+            .map(|(_, callback)| callback.clone())
+            .collect();
+
+        Some((snapshot.clone(), callbacks))
+    }
+
+    pub(crate) fn subscribe(&mut self, id: &str, callback: &SensorCallback) -> SubscriptionId {
+        let subscription_id = SubscriptionId(self.next_subscription);
+        self.next_subscription += 1;
+        self.subscriptions
+            .entry(id.to_string())
+            .or_default()
+            .push((subscription_id, callback.clone()));
+        subscription_id
+    }
+
+    pub(crate) fn subscribe_all(&mut self, callback: &SensorCallback) -> SubscriptionId {
+        let subscription_id = SubscriptionId(self.next_subscription);
+        self.next_subscription += 1;
+        self.all_subscriptions
+            .push((subscription_id, callback.clone()));
+        subscription_id
+    }
+
+    pub(crate) fn unsubscribe(&mut self, subscription_id: SubscriptionId) {
+        self.all_subscriptions
+            .retain(|(id, _)| *id != subscription_id);
+        for subscriptions in self.subscriptions.values_mut() {
+            subscriptions.retain(|(id, _)| *id != subscription_id);
+        }
+        self.subscriptions
+            .retain(|_, subscriptions| !subscriptions.is_empty());
+    }
+}
@@ -1,13 +1,180 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::units::Units;
+use crate::GenericError;
+
+/// Written in config files as its [`SensorKind::name`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SensorKind {
+    Fan,
+    Temp,
+    Voltage,
+    Current,
+    Power,
+    Frequency,
+    Utilisation,
+    Energy,
+    /// Sensors in units that aren't recognised.
+    Other,
+}
+
+impl SensorKind {
+    pub const ALL: [SensorKind; 9] = [
+        SensorKind::Temp,
+        SensorKind::Fan,
+        SensorKind::Power,
+        SensorKind::Voltage,
+        SensorKind::Current,
+        SensorKind::Frequency,
+        SensorKind::Utilisation,
+        SensorKind::Energy,
+        SensorKind::Other,
+    ];
+
+    /// A short lowercase name, e.g. "temp".
+    pub fn name(&self) -> &'static str {
+        match self {
+            SensorKind::Fan => "fan",
+            SensorKind::Temp => "temp",
+            SensorKind::Voltage => "voltage",
+            SensorKind::Current => "current",
+            SensorKind::Power => "power",
+            SensorKind::Frequency => "frequency",
+            SensorKind::Utilisation => "utilisation",
+            SensorKind::Energy => "energy",
+            SensorKind::Other => "other",
+        }
+    }
+
+    /// What this kind of sensor measures, e.g. "Temperature".
+    pub fn quantity(&self) -> &'static str {
+        match self {
+            SensorKind::Fan => "Fan Speed",
+            SensorKind::Temp => "Temperature",
+            SensorKind::Voltage => "Voltage",
+            SensorKind::Current => "Current",
+            SensorKind::Power => "Power",
+            SensorKind::Frequency => "Frequency",
+            SensorKind::Utilisation => "Utilisation",
+            SensorKind::Energy => "Energy",
+            SensorKind::Other => "Reading",
+        }
+    }
+
+    /// The units this kind of sensor usually reports in.
+    pub fn units(&self) -> Units {
+        match self {
+            SensorKind::Fan => Units::Rpm,
+            SensorKind::Temp => Units::Celsius,
+            SensorKind::Voltage => Units::Volts,
+            SensorKind::Current => Units::Amps,
+            SensorKind::Power => Units::Watts,
+            SensorKind::Frequency => Units::Megahertz,
+            SensorKind::Utilisation => Units::Percent,
+            SensorKind::Energy => Units::Joules,
+            SensorKind::Other => Units::Other("".to_string()),
+        }
+    }
+}
+
+/// How much a reading can be trusted to reflect what the sensor currently reads.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReadingStatus {
+    Fresh,
+    /// Nothing new has arrived for longer than the engine's stale timeout.
+    Stale,
+    /// Nothing has arrived yet, so the value is meaningless.
+    NeverReceived,
+}
+
+impl ReadingStatus {
+    /// The status of a reading last `updated` at the given time.
+    pub fn of(updated: Option<Instant>, stale_after: Duration) -> ReadingStatus {
+        match updated {
+            None => ReadingStatus::NeverReceived,
+            Some(updated) if updated.elapsed() > stale_after => ReadingStatus::Stale,
+            Some(_) => ReadingStatus::Fresh,
+        }
+    }
+}
+
+/// A single value, either from one sensor or aggregated from several.
 #[derive(Clone, Debug, PartialEq)]
-pub struct Temp {
+pub struct Reading {
     pub label: String,
     pub value: f64,
-    pub units: String,
+    pub units: Units,
+    pub updated: Option<Instant>,
+    pub status: ReadingStatus,
 }
 
+impl Reading {
+    /// A `kind` reading that hasn't been received yet.
+    pub fn never_received(kind: SensorKind) -> Reading {
+        Reading {
+            label: "".to_string(),
+            value: 0.0,
+            units: kind.units(),
+            updated: None,
+            status: ReadingStatus::NeverReceived,
+        }
+    }
+}
+
+/// Everything known about one sensor, including its latest reading if there's been one.
 #[derive(Clone, Debug, PartialEq)]
-pub struct Fan {
+pub struct SensorSnapshot {
+    pub id: String,
     pub label: String,
-    pub value: f64,
-    pub units: String,
+    pub kind: SensorKind,
+    pub units: Units,
+    pub value: Option<f64>,
+    pub updated: Option<Instant>,
+    pub status: ReadingStatus,
+}
+
+/// Identifies a subscription so that it can be cancelled.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SubscriptionId(pub(crate) u64);
+
+pub type SensorCallback =
+    Arc<Mutex<Box<dyn Fn(SensorSnapshot) -> Result<(), GenericError> + Send + Sync + 'static>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STALE_AFTER: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn a_reading_never_received_is_waiting() {
+        assert_eq!(
+            ReadingStatus::of(None, STALE_AFTER),
+            ReadingStatus::NeverReceived
+        );
+    }
+
+    #[test]
+    fn a_recent_reading_is_fresh() {
+        let updated = Instant::now() - Duration::from_secs(29);
+
+        assert_eq!(
+            ReadingStatus::of(Some(updated), STALE_AFTER),
+            ReadingStatus::Fresh
+        );
+    }
+
+    #[test]
+    fn an_old_reading_is_stale() {
+        let updated = Instant::now() - Duration::from_secs(31);
+
+        assert_eq!(
+            ReadingStatus::of(Some(updated), STALE_AFTER),
+            ReadingStatus::Stale
+        );
+    }
 }
@@ -1,45 +1,34 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use std::thread::spawn;
+use std::time::Instant;
 
-use log::error;
-use zbus::blocking::fdo::PropertiesProxy;
-use zbus::blocking::{Connection, PropertyIterator};
-use zbus::names::InterfaceName;
-use zbus::CacheProperties;
-
-use crate::config::INDICATOR_SENSORS_SERVICE;
-use crate::metric::Metric;
+use crate::aggregation::Aggregation;
 use crate::mutex_helpers::lock;
+use crate::units::Units;
 use crate::GenericError;
 
-type Callback = Arc<
-    Mutex<Box<dyn Fn(String, f64, String) -> Result<(), GenericError> + Send + Sync + 'static>>,
+pub(crate) type Callback = Arc<
+    Mutex<
+        Box<
+            dyn Fn(String, f64, Units, Option<Instant>) -> Result<(), GenericError>
+                + Send
+                + Sync
+                + 'static,
+        >,
+    >,
 >;
 type OptionalCallback = Option<Callback>;
 
+#[derive(Clone)]
 pub(crate) struct SortedPropertyObserverBuilder {
+    aggregation: Aggregation,
     callback: OptionalCallback,
-    metrics: Vec<Metric>,
-}
-
-impl Clone for SortedPropertyObserverBuilder {
-    fn clone(&self) -> Self {
-        SortedPropertyObserverBuilder {
-            callback: if self.callback.is_some() {
-                Some(self.callback.as_ref().unwrap().clone())
-            } else {
-                None
-            },
-            metrics: self.metrics.clone(),
-        }
-    }
 }
 
 pub(crate) fn builder() -> SortedPropertyObserverBuilder {
     SortedPropertyObserverBuilder {
+        aggregation: Aggregation::Max,
         callback: None,
-        metrics: vec![],
     }
 }
 
@@ -48,16 +37,21 @@ impl SortedPropertyObserverBuilder {
         self
     }
 
-    pub(crate) fn build(&mut self) -> Result<(), GenericError> {
-        spawn_workers(self)?;
-        Ok(())
+    pub(crate) fn build(&mut self) -> Result<SortedPropertyObserver, GenericError> {
+        Ok(SortedPropertyObserver {
+            state: Arc::new(Mutex::new(State {
+                builder: self.clone(),
+                current: PropertyValue::empty(),
+                samples: HashMap::new(),
+            })),
+        })
     }
 
-    pub(crate) fn with_metrics(
+    pub(crate) fn with_aggregation(
         &mut self,
-        metrics: &Vec<Metric>,
+        aggregation: &Aggregation,
     ) -> &mut SortedPropertyObserverBuilder {
-        self.metrics = metrics.clone();
+        self.aggregation = aggregation.clone();
         self
     }
 
@@ -70,118 +64,94 @@ impl SortedPropertyObserverBuilder {
     }
 }
 
-fn spawn_workers(source: &SortedPropertyObserverBuilder) -> Result<(), GenericError> {
-    let state = Arc::new(Mutex::new(State {
-        builder: (*source).clone(),
-        max: PropertyValue {
-            name: "".to_string(),
-            units: "".to_string(),
-            value: 0.0,
-        },
-        samples: HashMap::new(),
-    }));
-
-    for metric in source.metrics.clone() {
-        let state_ref = state.clone();
-        spawn(move || run(&metric, state_ref).expect("property handler thread runs"));
-    }
-
-    Ok(())
+/// Keeps the latest sample from each of a group of sensors and reports their aggregate to the on
+/// change callback.
+#[derive(Clone)]
+pub(crate) struct SortedPropertyObserver {
+    state: Arc<Mutex<State>>,
 }
 
-fn make_property<'a, 'b: 'a>(
-    connection: &'a Connection,
-    metric: &'b Metric,
-) -> Result<PropertiesProxy<'a>, GenericError> {
-    let value = metric.get_value();
-    let path = &value.dbus_info.path;
-    Ok(PropertiesProxy::builder(&connection)
-        .cache_properties(CacheProperties::Lazily)
-        .destination(INDICATOR_SENSORS_SERVICE)?
-        .path(path.as_str())?
-        .interface(InterfaceName::try_from(
-            (&value).dbus_info.interface_name.as_str(),
-        )?)?
-        .build()?)
-}
+impl SortedPropertyObserver {
+    pub(crate) fn update(
+        &self,
+        id: &str,
+        name: &str,
+        value: f64,
+        units: &Units,
+        updated: Option<Instant>,
+    ) -> Result<(), GenericError> {
+        let mut locked_state = lock(&self.state)?;
+        locked_state.insert(&PropertyValue {
+            id: id.to_string(),
+            name: name.to_string(),
+            units: units.clone(),
+            value,
+            updated,
+        })
+    }
 
-fn run(metric: &Metric, state: Arc<Mutex<State>>) -> Result<(), GenericError> {
-    {
-        let connection = Connection::session().map_err(|e| {
-            error!("zbus signal: {e}");
-            e
-        })?;
-
-        let value = metric.get_value().clone();
-        let property = make_property(&connection, metric)?;
-        let mut changed_signal: PropertyIterator<f64> = property.receive_property_changed("Value");
-        println!(
-            "listening for {} = {}{}",
-            value.label.clone(),
-            value.value,
-            value.units
-        );
-        update(
-            &state,
-            PropertyValue {
-                name: value.label.clone(),
-                units: value.units.clone(),
-                value: value.value,
-            },
-        )?;
-        loop {
-            let change = changed_signal.next().unwrap();
-            println!(
-                "{} changed to {}{}",
-                value.label,
-                change.get()?,
-                value.units
-            );
-
-            update(
-                &state,
-                PropertyValue {
-                    name: value.label.clone(),
-                    units: value.units.clone(),
-                    value: change.get()?,
-                },
-            )?;
-        }
+    pub(crate) fn remove(&self, id: &str) -> Result<(), GenericError> {
+        lock(&self.state)?.remove(id)
     }
-}
 
-fn update(state: &Arc<Mutex<State>>, property_value: PropertyValue) -> Result<(), GenericError> {
-    let mut locked_state = lock(&state)?;
-    locked_state.insert(&property_value)?;
-    Ok(())
+    pub(crate) fn set_aggregation(&self, aggregation: &Aggregation) -> Result<(), GenericError> {
+        let mut locked_state = lock(&self.state)?;
+        locked_state.builder.aggregation = aggregation.clone();
+        locked_state.refresh()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 struct PropertyValue {
+    id: String,
     name: String,
-    units: String,
+    units: Units,
     value: f64,
+    updated: Option<Instant>,
+}
+
+impl PropertyValue {
+    fn empty() -> PropertyValue {
+        PropertyValue {
+            id: "".to_string(),
+            name: "".to_string(),
+            units: Units::Other("".to_string()),
+            value: 0.0,
+            updated: None,
+        }
+    }
 }
 
 #[derive(Clone)]
 struct State {
     builder: SortedPropertyObserverBuilder,
-    max: PropertyValue,
+    current: PropertyValue,
     samples: HashMap<String, PropertyValue>,
 }
 
 impl State {
     fn insert(&mut self, property_value: &PropertyValue) -> Result<(), GenericError> {
         self.samples
-            .insert(property_value.name.clone(), property_value.clone());
-        let old_max = &self.max;
-        let new_max = self.max();
-
-        if let Some(max) = new_max {
-            if old_max != max {
-                self.max = max.clone();
-                self.call_callback()?;
-            }
+            .insert(property_value.id.clone(), property_value.clone());
+        self.refresh()
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), GenericError> {
+        if self.samples.remove(id).is_some() {
+            self.refresh()?;
+        }
+
+        Ok(())
+    }
+
+    /// Recomputes the aggregate, reporting it if it changed. An empty group reports an empty value.
+    ///
+    /// A new timestamp counts as a change, so that a steady reading doesn't go stale.
+    fn refresh(&mut self) -> Result<(), GenericError> {
+        let new_current = self.aggregate().unwrap_or_else(PropertyValue::empty);
+        if self.current != new_current {
+            self.current = new_current;
+            self.call_callback()?;
         }
 
         Ok(())
@@ -189,18 +159,260 @@ impl State {
 
     fn call_callback(&mut self) -> Result<(), GenericError> {
         if let Some(callback) = &self.builder.callback {
-            if let Some(max) = self.max() {
-                let locked_callback = lock(&callback)?;
-                locked_callback(max.name.clone(), max.value, max.units.clone())?;
-            }
+            let locked_callback = lock(callback)?;
+            locked_callback(
+                self.current.name.clone(),
+                self.current.value,
+                self.current.units.clone(),
+                self.current.updated,
+            )?;
         }
 
         Ok(())
     }
 
-    fn max(&self) -> Option<&PropertyValue> {
-        self.samples
-            .values()
-            .max_by(|left, right| left.value.total_cmp(&right.value))
+    fn aggregate(&self) -> Option<PropertyValue> {
+        let mut samples = self.samples.values();
+        match &self.builder.aggregation {
+            Aggregation::Max => samples
+                .max_by(|left, right| left.value.total_cmp(&right.value))
+                .cloned(),
+            Aggregation::Min => samples
+                .min_by(|left, right| left.value.total_cmp(&right.value))
+                .cloned(),
+            Aggregation::Mean => weighted_mean(samples.map(|sample| (sample, 1.0))),
+            Aggregation::Median => {
+                let mut sorted: Vec<&PropertyValue> = samples.collect();
+                sorted.sort_by(|left, right| left.value.total_cmp(&right.value));
+                let middle = sorted.len() / 2;
+                match sorted.len() {
+                    0 => None,
+                    len if len % 2 == 1 => Some(sorted[middle].clone()),
+                    _ => weighted_mean(
+                        sorted[middle - 1..=middle]
+                            .iter()
+                            .map(|sample| (*sample, 1.0)),
+                    ),
+                }
+            }
+            Aggregation::Pinned(sensor) => samples
+                .find(|sample| sample.id == *sensor || sample.name == *sensor)
+                .cloned(),
+            Aggregation::WeightedMean(weights) => weighted_mean(samples.filter_map(|sample| {
+                weights
+                    .get(&sample.id)
+                    .or_else(|| weights.get(&sample.name))
+                    .map(|weight| (sample, *weight))
+            })),
+        }
+    }
+}
+
+/// Combines several samples into one without a label, as it doesn't come from any single sensor.
+/// It's only as recent as the oldest of them that has a timestamp.
+fn weighted_mean<'a>(
+    weighted_samples: impl Iterator<Item = (&'a PropertyValue, f64)>,
+) -> Option<PropertyValue> {
+    let mut units = None;
+    let mut updated: Option<Instant> = None;
+    let mut total = 0.0;
+    let mut total_weight = 0.0;
+    for (sample, weight) in weighted_samples {
+        units.get_or_insert_with(|| sample.units.clone());
+        if let Some(sample_updated) = sample.updated {
+            updated = Some(updated.map_or(sample_updated, |oldest| oldest.min(sample_updated)));
+        }
+        total += sample.value * weight;
+        total_weight += weight;
+    }
+
+    units
+        .filter(|_| total_weight != 0.0)
+        .map(|units| PropertyValue {
+            id: "".to_string(),
+            name: "".to_string(),
+            units,
+            value: total / total_weight,
+            updated,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn sample(id: &str, name: &str, value: f64, updated: Option<Instant>) -> PropertyValue {
+        PropertyValue {
+            id: id.to_string(),
+            name: name.to_string(),
+            units: Units::Celsius,
+            value,
+            updated,
+        }
+    }
+
+    fn aggregate(aggregation: Aggregation, samples: &[PropertyValue]) -> Option<PropertyValue> {
+        let state = State {
+            builder: builder().with_aggregation(&aggregation).clone(),
+            current: PropertyValue::empty(),
+            samples: samples
+                .iter()
+                .map(|sample| (sample.id.clone(), sample.clone()))
+                .collect(),
+        };
+        state.aggregate()
+    }
+
+    fn value(aggregation: Aggregation, samples: &[PropertyValue]) -> Option<f64> {
+        aggregate(aggregation, samples).map(|aggregate| aggregate.value)
+    }
+
+    fn three() -> Vec<PropertyValue> {
+        vec![
+            sample("/cpu", "CPU", 60.0, None),
+            sample("/gpu", "GPU", 40.0, None),
+            sample("/nvme", "NVMe", 35.0, None),
+        ]
+    }
+
+    fn weights(weights: &[(&str, f64)]) -> Aggregation {
+        Aggregation::WeightedMean(
+            weights
+                .iter()
+                .map(|(sensor, weight)| (sensor.to_string(), *weight))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn max_and_min_keep_the_sensors_label() {
+        let max = aggregate(Aggregation::Max, &three()).unwrap();
+        assert_eq!((max.name.as_str(), max.value), ("CPU", 60.0));
+        let min = aggregate(Aggregation::Min, &three()).unwrap();
+        assert_eq!((min.name.as_str(), min.value), ("NVMe", 35.0));
+    }
+
+    #[test]
+    fn mean_averages_every_sensor_without_a_label() {
+        let mean = aggregate(Aggregation::Mean, &three()).unwrap();
+
+        assert_eq!(mean.value, 45.0);
+        assert_eq!(mean.name, "");
+        assert_eq!(mean.units, Units::Celsius);
+    }
+
+    #[test]
+    fn median_of_an_odd_count_is_the_middle_sensor() {
+        let median = aggregate(Aggregation::Median, &three()).unwrap();
+
+        assert_eq!((median.name.as_str(), median.value), ("GPU", 40.0));
+    }
+
+    #[test]
+    fn median_of_an_even_count_averages_the_middle_two() {
+        let mut samples = three();
+        samples.push(sample("/vrm", "VRM", 50.0, None));
+
+        assert_eq!(value(Aggregation::Median, &samples), Some(45.0));
+    }
+
+    #[test]
+    fn pinned_picks_a_sensor_by_id_or_label() {
+        let by_id = aggregate(Aggregation::Pinned("/gpu".to_string()), &three()).unwrap();
+        assert_eq!(by_id.value, 40.0);
+        let by_label = aggregate(Aggregation::Pinned("NVMe".to_string()), &three()).unwrap();
+        assert_eq!(by_label.value, 35.0);
+        assert_eq!(
+            value(Aggregation::Pinned("/missing".to_string()), &three()),
+            None
+        );
+    }
+
+    #[test]
+    fn weighted_mean_ignores_sensors_without_a_weight() {
+        assert_eq!(
+            value(weights(&[("CPU", 3.0), ("/gpu", 1.0)]), &three()),
+            Some(55.0)
+        );
+    }
+
+    #[test]
+    fn weighted_mean_with_all_zero_weights_is_unset() {
+        assert_eq!(
+            value(weights(&[("CPU", 0.0), ("GPU", 0.0)]), &three()),
+            None
+        );
+    }
+
+    #[test]
+    fn every_reduction_of_no_sensors_is_unset() {
+        let aggregations = [
+            Aggregation::Max,
+            Aggregation::Min,
+            Aggregation::Mean,
+            Aggregation::Median,
+            Aggregation::Pinned("CPU".to_string()),
+            weights(&[("CPU", 1.0)]),
+        ];
+        for aggregation in aggregations {
+            assert_eq!(aggregate(aggregation.clone(), &[]), None, "{aggregation:?}");
+        }
+    }
+
+    #[test]
+    fn a_mean_is_as_recent_as_its_oldest_timestamped_sample() {
+        let now = Instant::now();
+        let older = now - Duration::from_secs(10);
+        let samples = vec![
+            sample("/cpu", "CPU", 60.0, Some(now)),
+            sample("/gpu", "GPU", 40.0, Some(older)),
+            sample("/nvme", "NVMe", 35.0, None),
+        ];
+
+        assert_eq!(
+            aggregate(Aggregation::Mean, &samples).unwrap().updated,
+            Some(older)
+        );
+    }
+
+    #[test]
+    fn reports_the_aggregate_when_it_changes() {
+        let reported = Arc::new(Mutex::new(vec![]));
+        let callback: Callback = {
+            let reported = reported.clone();
+            Arc::new(Mutex::new(Box::new(
+                move |name: String, value: f64, _: Units, _: Option<Instant>| {
+                    reported.lock().unwrap().push((name, value));
+                    Ok(())
+                },
+            )))
+        };
+        let observer = builder()
+            .with_aggregation(&Aggregation::Max)
+            .and()
+            .with_on_change_callback(&callback)
+            .and()
+            .build()
+            .unwrap();
+
+        observer
+            .update("/cpu", "CPU", 60.0, &Units::Celsius, None)
+            .unwrap();
+        observer
+            .update("/gpu", "GPU", 40.0, &Units::Celsius, None)
+            .unwrap();
+        observer.remove("/cpu").unwrap();
+        observer.remove("/gpu").unwrap();
+
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![
+                ("CPU".to_string(), 60.0),
+                ("GPU".to_string(), 40.0),
+                ("".to_string(), 0.0),
+            ]
+        );
     }
 }
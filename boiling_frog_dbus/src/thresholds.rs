@@ -0,0 +1,75 @@
+use serde::Deserialize;
+
+/// How worrying a reading is, in increasing order.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Level {
+    #[default]
+    Normal,
+    Warm,
+    Hot,
+    Critical,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Normal, Level::Warm, Level::Hot, Level::Critical];
+
+    /// A short lowercase name, e.g. "warm", which is also used as a CSS class.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Normal => "normal",
+            Level::Warm => "warm",
+            Level::Hot => "hot",
+            Level::Critical => "critical",
+        }
+    }
+}
+
+/// The values at which a kind of reading becomes [`Level::Warm`], [`Level::Hot`] &
+/// [`Level::Critical`], in the kind's [`crate::simple_types::SensorKind::units`].
+///
+/// In a config file, this is e.g. `{ warm = 70, hot = 85, critical = 95 }`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Thresholds {
+    pub warm: f64,
+    pub hot: f64,
+    pub critical: f64,
+}
+
+impl Thresholds {
+    pub fn level(&self, value: f64) -> Level {
+        if value >= self.critical {
+            Level::Critical
+        } else if value >= self.hot {
+            Level::Hot
+        } else if value >= self.warm {
+            Level::Warm
+        } else {
+            Level::Normal
+        }
+    }
+
+    /// The value at which readings become `level`, or `None` for [`Level::Normal`].
+    pub fn threshold(&self, level: Level) -> Option<f64> {
+        match level {
+            Level::Normal => None,
+            Level::Warm => Some(self.warm),
+            Level::Hot => Some(self.hot),
+            Level::Critical => Some(self.critical),
+        }
+    }
+
+    /// How far `value` is towards critical, from 0 to 1.
+    pub fn fraction(&self, value: f64) -> f64 {
+        if self.critical > 0.0 {
+            (value / self.critical).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Whether the thresholds are in increasing order.
+    pub fn is_ordered(&self) -> bool {
+        self.warm <= self.hot && self.hot <= self.critical
+    }
+}
@@ -0,0 +1,164 @@
+use std::iter::once;
+use std::time::{Duration, Instant};
+
+use crate::history::Sample;
+
+/// When a kind of reading counts as rising steadily.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrendSettings {
+    /// The slowest rise that counts, in the kind's units per minute.
+    pub rate: f64,
+    /// How long the readings have to have been rising at `rate`, on average.
+    pub period: Duration,
+}
+
+/// How a reading has been changing over its kind's [`TrendSettings::period`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trend {
+    /// The slope of the least-squares line through the readings, in the kind's units per minute.
+    pub rate: f64,
+    /// How long the reading will take to become critical at `rate`, if it's rising & isn't
+    /// critical yet.
+    pub time_to_critical: Option<Duration>,
+}
+
+impl Trend {
+    /// Fits a line through `samples` from `period` before `now`, as [`crate::DbusEngine::history`]
+    /// returns them, or `None` if they don't go back that far.
+    pub(crate) fn fit(
+        samples: &[Sample],
+        now: Instant,
+        period: Duration,
+        critical: Option<f64>,
+    ) -> Option<Trend> {
+        let from = now.checked_sub(period)?;
+        let (first, latest) = (samples.first()?, samples.last()?);
+        if first.time > from {
+            return None;
+        }
+
+        // Each value holds until the next, so the one current at `from` starts there & the latest
+        // lasts until now.
+        let points: Vec<(f64, f64)> = samples
+            .iter()
+            .map(|sample| (sample.time.max(from), sample.value))
+            .chain(once((now, latest.value)))
+            .map(|(time, value)| {
+                let minutes = time.saturating_duration_since(from).as_secs_f64() / 60.0;
+                (minutes, value)
+            })
+            .collect();
+        let count = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+        let (covariance, variance) =
+            points
+                .iter()
+                .fold((0.0, 0.0), |(covariance, variance), (x, y)| {
+                    (
+                        covariance + (x - mean_x) * (y - mean_y),
+                        variance + (x - mean_x) * (x - mean_x),
+                    )
+                });
+        if variance <= 0.0 {
+            return None;
+        }
+
+        let rate = covariance / variance;
+        let time_to_critical = critical
+            .filter(|critical| rate > 0.0 && latest.value < *critical)
+            .and_then(|critical| {
+                Duration::try_from_secs_f64((critical - latest.value) / rate * 60.0).ok()
+            });
+
+        Some(Trend {
+            rate,
+            time_to_critical,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_secs(60);
+
+    /// A reading every 10 seconds from `seconds_ago` until `now`, changing at `rate` per minute
+    /// to reach `latest`.
+    fn samples(now: Instant, seconds_ago: u64, rate: f64, latest: f64) -> Vec<Sample> {
+        (0..=seconds_ago / 10)
+            .rev()
+            .map(|tens| {
+                let ago = Duration::from_secs(tens * 10);
+                let value = latest - rate * ago.as_secs_f64() / 60.0;
+                Sample {
+                    time: now - ago,
+                    value,
+                    min: value,
+                    max: value,
+                }
+            })
+            .collect()
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{actual} isn't {expected}"
+        );
+    }
+
+    #[test]
+    fn fits_the_rate_of_a_steady_rise() {
+        let now = Instant::now();
+        let trend = Trend::fit(&samples(now, 60, 6.0, 50.0), now, PERIOD, None).unwrap();
+
+        assert_near(trend.rate, 6.0);
+        assert_eq!(trend.time_to_critical, None);
+    }
+
+    #[test]
+    fn ignores_readings_from_before_the_period() {
+        let now = Instant::now();
+        let mut samples = samples(now, 60, 6.0, 50.0);
+        // Holds until the next reading, so it only counts as the value at the start.
+        samples[0].time = now - Duration::from_secs(600);
+
+        let trend = Trend::fit(&samples, now, PERIOD, None).unwrap();
+        assert_near(trend.rate, 6.0);
+    }
+
+    #[test]
+    fn estimates_the_time_until_critical() {
+        let now = Instant::now();
+        let trend = Trend::fit(&samples(now, 60, 6.0, 50.0), now, PERIOD, Some(62.0)).unwrap();
+
+        assert_eq!(trend.time_to_critical, Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn has_no_time_until_critical_unless_rising_below_it() {
+        let now = Instant::now();
+        let falling = Trend::fit(&samples(now, 60, -6.0, 50.0), now, PERIOD, Some(62.0)).unwrap();
+        let critical = Trend::fit(&samples(now, 60, 6.0, 70.0), now, PERIOD, Some(62.0)).unwrap();
+        let steady = Trend::fit(&samples(now, 60, 0.0, 50.0), now, PERIOD, Some(62.0)).unwrap();
+
+        assert_near(falling.rate, -6.0);
+        assert_eq!(falling.time_to_critical, None);
+        assert_eq!(critical.time_to_critical, None);
+        assert_near(steady.rate, 0.0);
+        assert_eq!(steady.time_to_critical, None);
+    }
+
+    #[test]
+    fn needs_readings_from_the_whole_period() {
+        let now = Instant::now();
+
+        assert_eq!(
+            Trend::fit(&samples(now, 50, 6.0, 50.0), now, PERIOD, None),
+            None
+        );
+        assert_eq!(Trend::fit(&[], now, PERIOD, None), None);
+    }
+}
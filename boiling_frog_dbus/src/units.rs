@@ -0,0 +1,281 @@
+use std::fmt::{Display, Formatter};
+
+use serde::Deserialize;
+
+use crate::simple_types::SensorKind;
+
+/// The units a reading is given in. Anything unrecognised is kept as it was reported.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Units {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    Rpm,
+    Volts,
+    Amps,
+    Watts,
+    Hertz,
+    Megahertz,
+    Percent,
+    Joules,
+    Other(String),
+}
+
+impl Units {
+    /// The kind of sensor that reports in these units.
+    pub fn kind(&self) -> SensorKind {
+        match self {
+            Units::Celsius | Units::Fahrenheit | Units::Kelvin => SensorKind::Temp,
+            Units::Rpm => SensorKind::Fan,
+            Units::Volts => SensorKind::Voltage,
+            Units::Amps => SensorKind::Current,
+            Units::Watts => SensorKind::Power,
+            Units::Hertz | Units::Megahertz => SensorKind::Frequency,
+            Units::Percent => SensorKind::Utilisation,
+            Units::Joules => SensorKind::Energy,
+            Units::Other(_) => SensorKind::Other,
+        }
+    }
+
+    /// The units readings in these units are converted to before they're aggregated, so that
+    /// e.g. Celsius & Fahrenheit temperatures can be compared.
+    pub fn canonical(&self) -> Units {
+        match self {
+            Units::Fahrenheit | Units::Kelvin => Units::Celsius,
+            Units::Hertz => Units::Megahertz,
+            units => units.clone(),
+        }
+    }
+
+    /// Converts `value` in these units to [`Units::canonical`] units.
+    pub fn to_canonical(&self, value: f64) -> f64 {
+        match self {
+            Units::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+            Units::Kelvin => value - 273.15,
+            Units::Hertz => value / 1_000_000.0,
+            _ => value,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            Units::Celsius => "℃",
+            Units::Fahrenheit => "℉",
+            Units::Kelvin => "K",
+            Units::Rpm => "RPM",
+            Units::Volts => "V",
+            Units::Amps => "A",
+            Units::Watts => "W",
+            Units::Hertz => "Hz",
+            Units::Megahertz => "MHz",
+            Units::Percent => "%",
+            Units::Joules => "J",
+            Units::Other(units) => units,
+        }
+    }
+}
+
+impl From<&str> for Units {
+    fn from(units: &str) -> Self {
+        match units.trim() {
+            "℃" | "°C" | "C" => Units::Celsius,
+            "℉" | "°F" | "F" => Units::Fahrenheit,
+            "K" => Units::Kelvin,
+            "RPM" | "rpm" => Units::Rpm,
+            "V" => Units::Volts,
+            "A" => Units::Amps,
+            "W" => Units::Watts,
+            "Hz" => Units::Hertz,
+            "MHz" => Units::Megahertz,
+            "%" => Units::Percent,
+            "J" => Units::Joules,
+            other => Units::Other(other.to_string()),
+        }
+    }
+}
+
+impl Display for Units {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// The scale temperatures are shown in.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureScale {
+    #[default]
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl TemperatureScale {
+    pub fn units(&self) -> Units {
+        match self {
+            TemperatureScale::Celsius => Units::Celsius,
+            TemperatureScale::Fahrenheit => Units::Fahrenheit,
+            TemperatureScale::Kelvin => Units::Kelvin,
+        }
+    }
+
+    /// Converts `value` in `units` to this scale if it's a temperature, leaving anything else as it
+    /// is.
+    pub fn apply(&self, value: f64, units: &Units) -> (f64, Units) {
+        if units.kind() != SensorKind::Temp {
+            return (value, units.clone());
+        }
+
+        let celsius = units.to_canonical(value);
+        let converted = match self {
+            TemperatureScale::Celsius => celsius,
+            TemperatureScale::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            TemperatureScale::Kelvin => celsius + 273.15,
+        };
+        (converted, self.units())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::backend::{Sensor, SensorBackend, SensorSink};
+    use crate::dbus_engine::DbusEngine;
+    use crate::GenericError;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn converts_temperatures_to_celsius() {
+        assert_eq!(Units::Fahrenheit.canonical(), Units::Celsius);
+        assert_eq!(Units::Kelvin.canonical(), Units::Celsius);
+        assert_close(Units::Fahrenheit.to_canonical(212.0), 100.0);
+        assert_close(
+            Units::Fahrenheit.to_canonical(120.0),
+            48.888_888_888_888_886,
+        );
+        assert_close(Units::Kelvin.to_canonical(273.15), 0.0);
+        assert_close(Units::Celsius.to_canonical(80.0), 80.0);
+    }
+
+    #[test]
+    fn converts_hertz_to_megahertz() {
+        assert_eq!(Units::Hertz.canonical(), Units::Megahertz);
+        assert_close(Units::Hertz.to_canonical(3_600_000_000.0), 3600.0);
+        assert_close(Units::Megahertz.to_canonical(3600.0), 3600.0);
+    }
+
+    #[test]
+    fn leaves_other_units_alone() {
+        assert_eq!(Units::Rpm.canonical(), Units::Rpm);
+        assert_close(Units::Rpm.to_canonical(1200.0), 1200.0);
+        let other = Units::Other("dB".to_string());
+        assert_eq!(other.canonical(), other);
+        assert_close(other.to_canonical(42.0), 42.0);
+    }
+
+    #[test]
+    fn parses_symbols() {
+        assert_eq!(Units::from("°F"), Units::Fahrenheit);
+        assert_eq!(Units::from("℉"), Units::Fahrenheit);
+        assert_eq!(Units::from("K"), Units::Kelvin);
+        assert_eq!(Units::from(" ℃ "), Units::Celsius);
+        assert_eq!(
+            Units::from("furlongs"),
+            Units::Other("furlongs".to_string())
+        );
+        assert_eq!(Units::from("furlongs").kind(), SensorKind::Other);
+    }
+
+    #[test]
+    fn applies_each_temperature_scale() {
+        let (value, units) = TemperatureScale::Celsius.apply(212.0, &Units::Fahrenheit);
+        assert_close(value, 100.0);
+        assert_eq!(units, Units::Celsius);
+
+        let (value, units) = TemperatureScale::Fahrenheit.apply(100.0, &Units::Celsius);
+        assert_close(value, 212.0);
+        assert_eq!(units, Units::Fahrenheit);
+
+        let (value, units) = TemperatureScale::Kelvin.apply(0.0, &Units::Celsius);
+        assert_close(value, 273.15);
+        assert_eq!(units, Units::Kelvin);
+    }
+
+    #[test]
+    fn applies_scales_only_to_temperatures() {
+        assert_eq!(
+            TemperatureScale::Fahrenheit.apply(1200.0, &Units::Rpm),
+            (1200.0, Units::Rpm)
+        );
+    }
+
+    /// Reports the sensors it's given & hands its sink to the test.
+    struct Fake {
+        sensors: Vec<Sensor>,
+        sink: Arc<Mutex<Option<SensorSink>>>,
+    }
+
+    impl SensorBackend for Fake {
+        fn name(&self) -> &str {
+            "fake"
+        }
+
+        fn discover(&mut self) -> Result<Vec<Sensor>, GenericError> {
+            Ok(self.sensors.clone())
+        }
+
+        fn start(&mut self, sink: SensorSink) -> Result<(), GenericError> {
+            *self.sink.lock().unwrap() = Some(sink);
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> Result<(), GenericError> {
+            Ok(())
+        }
+    }
+
+    fn sensor(id: &str, units: Units) -> Sensor {
+        Sensor {
+            id: id.to_string(),
+            path: id.to_string(),
+            interface: "fake".to_string(),
+            label: id.to_string(),
+            kind: SensorKind::Temp,
+            units,
+        }
+    }
+
+    #[test]
+    fn compares_fahrenheit_readings_in_celsius() {
+        let sink = Arc::new(Mutex::new(None));
+        let engine = DbusEngine::builder()
+            .with_backend(Box::new(Fake {
+                sensors: vec![
+                    sensor("/celsius", Units::Celsius),
+                    sensor("/fahrenheit", Units::Fahrenheit),
+                ],
+                sink: sink.clone(),
+            }))
+            .and()
+            .build()
+            .unwrap();
+        let sink = sink.lock().unwrap().clone().unwrap();
+
+        sink.update("/celsius", 80.0, &Units::Celsius).unwrap();
+        sink.update("/fahrenheit", 120.0, &Units::Fahrenheit)
+            .unwrap();
+
+        let reading = engine.temp().unwrap();
+        assert_eq!(reading.label, "/celsius");
+        assert_close(reading.value, 80.0);
+        assert_eq!(reading.units, Units::Celsius);
+        let fahrenheit = engine.sensor("/fahrenheit").unwrap().unwrap();
+        assert_close(fahrenheit.value.unwrap(), 48.888_888_888_888_886);
+        assert_eq!(fahrenheit.units, Units::Celsius);
+    }
+}